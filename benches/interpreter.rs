@@ -0,0 +1,362 @@
+use brainfuck::{
+	compile, execute, profile, BufferMode, Bytecode, CellWidth, Config, Instruction, TapeMode,
+};
+use std::{
+	collections::VecDeque,
+	env,
+	fs::{self, File},
+	io, iter,
+	time::Instant,
+};
+
+struct Benchmark {
+	name: &'static str,
+	source_code: &'static str,
+	input: &'static [u8],
+	expected_output: &'static [u8],
+	iterations: u32,
+	cell_width: CellWidth,
+	tape_mode: TapeMode,
+	preallocate_tape: bool,
+	compare_tapes: bool,
+	compare_buffering: bool,
+}
+
+const BENCHMARKS: &[Benchmark] = &[
+	Benchmark {
+		name: "hello_world",
+		source_code: include_str!("../samples/hello_world.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/hello_world.txt"),
+		iterations: 10_000,
+		cell_width: CellWidth::U8,
+		tape_mode: TapeMode::Growable,
+		preallocate_tape: false,
+		compare_tapes: false,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "primes",
+		source_code: include_str!("../samples/primes.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/primes.txt"),
+		iterations: 10,
+		cell_width: CellWidth::U8,
+		tape_mode: TapeMode::Growable,
+		preallocate_tape: false,
+		compare_tapes: true,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "mandelbrot",
+		source_code: include_str!("../samples/mandelbrot.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/mandelbrot.txt"),
+		iterations: 1,
+		cell_width: CellWidth::U8,
+		tape_mode: TapeMode::Growable,
+		preallocate_tape: false,
+		compare_tapes: true,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "left_walk",
+		source_code: include_str!("../samples/left_walk.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/left_walk.txt"),
+		iterations: 10,
+		cell_width: CellWidth::U32,
+		tape_mode: TapeMode::Growable,
+		preallocate_tape: false,
+		compare_tapes: true,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "right_fill",
+		source_code: include_str!("../samples/right_fill.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/right_fill.txt"),
+		iterations: 10,
+		cell_width: CellWidth::U32,
+		tape_mode: TapeMode::Fixed(4096),
+		preallocate_tape: false,
+		compare_tapes: false,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "right_fill_preallocated",
+		source_code: include_str!("../samples/right_fill.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/right_fill.txt"),
+		iterations: 10,
+		cell_width: CellWidth::U32,
+		tape_mode: TapeMode::Fixed(4096),
+		preallocate_tape: true,
+		compare_tapes: false,
+		compare_buffering: false,
+	},
+	Benchmark {
+		name: "newlines",
+		source_code: include_str!("../samples/newlines.bf"),
+		input: b"",
+		expected_output: include_bytes!("expected/newlines.txt"),
+		iterations: 10,
+		cell_width: CellWidth::U8,
+		tape_mode: TapeMode::Growable,
+		preallocate_tape: false,
+		compare_tapes: false,
+		compare_buffering: true,
+	},
+];
+
+/// A growable tape for the reference interpreter, so tape layouts can be compared with the rest
+/// of the interpreter held the same.
+trait Tape {
+	fn new() -> Self;
+	fn cell(&mut self) -> &mut u32;
+	fn move_right(&mut self, amount: usize);
+	fn move_left(&mut self, amount: usize);
+
+	fn move_by(&mut self, offset: isize) {
+		if offset < 0 {
+			self.move_left(offset.unsigned_abs());
+		} else {
+			self.move_right(offset.unsigned_abs());
+		}
+	}
+}
+
+/// The tape the interpreter used before it kept its cells in a `Vec` with an origin: a
+/// `VecDeque` that grows to the left one `push_front` at a time.
+struct VecDequeTape {
+	memory: VecDeque<u32>,
+	data_pointer: usize,
+}
+
+impl Tape for VecDequeTape {
+	fn new() -> Self {
+		Self {
+			memory: VecDeque::from(vec![0]),
+			data_pointer: 0,
+		}
+	}
+
+	fn cell(&mut self) -> &mut u32 {
+		&mut self.memory[self.data_pointer]
+	}
+
+	fn move_right(&mut self, amount: usize) {
+		self.data_pointer += amount;
+		if self.data_pointer >= self.memory.len() {
+			self.memory.resize(self.data_pointer + 1, 0);
+		}
+	}
+
+	fn move_left(&mut self, amount: usize) {
+		if amount > self.data_pointer {
+			for _ in 0..amount - self.data_pointer {
+				self.memory.push_front(0);
+			}
+			self.data_pointer = 0;
+		} else {
+			self.data_pointer -= amount;
+		}
+	}
+}
+
+/// The layout of the interpreter's own tape: a `Vec` that at least doubles when the pointer
+/// runs off either end.
+struct VecTape {
+	memory: Vec<u32>,
+	data_pointer: usize,
+}
+
+impl Tape for VecTape {
+	fn new() -> Self {
+		Self {
+			memory: vec![0],
+			data_pointer: 0,
+		}
+	}
+
+	fn cell(&mut self) -> &mut u32 {
+		&mut self.memory[self.data_pointer]
+	}
+
+	fn move_right(&mut self, amount: usize) {
+		self.data_pointer += amount;
+		if self.data_pointer >= self.memory.len() {
+			let len = (self.data_pointer + 1).max(self.memory.len() * 2);
+			self.memory.resize(len, 0);
+		}
+	}
+
+	fn move_left(&mut self, amount: usize) {
+		if amount > self.data_pointer {
+			let extra = (amount - self.data_pointer).max(self.memory.len());
+			self.memory.splice(0..0, iter::repeat_n(0, extra));
+			self.data_pointer += extra;
+		}
+		self.data_pointer -= amount;
+	}
+}
+
+/// Runs bytecode without input on a growable tape and collects its output.
+fn run_on<T: Tape>(instructions: &[Instruction], cell_width: CellWidth) -> Vec<u8> {
+	let max_value = cell_width.max_value();
+	let mut tape = T::new();
+	let mut output = Vec::new();
+	let mut instruction_pointer = 0;
+	while let Some(instruction) = instructions.get(instruction_pointer) {
+		match *instruction {
+			Instruction::IncPointer => tape.move_right(1),
+			Instruction::DecPointer => tape.move_left(1),
+			Instruction::AddPointer(amount) => tape.move_right(amount),
+			Instruction::SubPointer(amount) => tape.move_left(amount),
+			Instruction::IncByte => *tape.cell() = tape.cell().wrapping_add(1) & max_value,
+			Instruction::DecByte => *tape.cell() = tape.cell().wrapping_sub(1) & max_value,
+			Instruction::AddByte(amount) => {
+				*tape.cell() = tape.cell().wrapping_add(amount) & max_value;
+			}
+			Instruction::SubByte(amount) => {
+				*tape.cell() = tape.cell().wrapping_sub(amount) & max_value;
+			}
+			Instruction::SetZero => *tape.cell() = 0,
+			Instruction::MulAdd { offset, factor } => {
+				let value = *tape.cell();
+				if value != 0 {
+					tape.move_by(offset);
+					let target = tape.cell();
+					*target = target.wrapping_add(value.wrapping_mul(factor)) & max_value;
+					tape.move_by(-offset);
+				}
+			}
+			Instruction::Output => output.push(*tape.cell() as u8),
+			Instruction::OpenBracket { jump_location } if *tape.cell() == 0 => {
+				instruction_pointer = jump_location;
+			}
+			Instruction::CloseBracket { jump_location } if *tape.cell() != 0 => {
+				instruction_pointer = jump_location;
+			}
+			Instruction::Halt => break,
+			_ => {}
+		}
+		instruction_pointer += 1;
+	}
+	output
+}
+
+/// Times `run_on` with the old `VecDeque` tape against the interpreter's `Vec` tape. The two
+/// take turns on every iteration, so a machine that speeds up or slows down during the run
+/// affects both alike.
+fn compare_tapes(benchmark: &Benchmark, instructions: &[Instruction]) {
+	let time = |run: fn(&[Instruction], CellWidth) -> Vec<u8>| {
+		let start = Instant::now();
+		let output = run(instructions, benchmark.cell_width);
+		let seconds = start.elapsed().as_secs_f64();
+		assert_eq!(
+			output, benchmark.expected_output,
+			"{} produced the wrong output on a reference tape",
+			benchmark.name
+		);
+		seconds
+	};
+	let (mut vec_deque, mut vec) = (0.0, 0.0);
+	for _ in 0..benchmark.iterations {
+		vec_deque += time(run_on::<VecDequeTape>);
+		vec += time(run_on::<VecTape>);
+	}
+	let iterations = f64::from(benchmark.iterations);
+	println!(
+		"{:<24} {:>10.4} s VecDeque tape {:>10.4} s Vec tape {:>8.2}x",
+		benchmark.name,
+		vec_deque / iterations,
+		vec / iterations,
+		vec_deque / vec
+	);
+}
+
+/// Times writing the output to a file with line buffering, which writes every newline through
+/// to the file on its own, against full buffering.
+fn compare_buffering(benchmark: &Benchmark, bytecode: &Bytecode, config: &Config) {
+	let path = env::temp_dir().join(format!("brainfuck-bench-{}", benchmark.name));
+	let time = |buffer_mode| {
+		let config = Config {
+			buffer_mode,
+			..config.clone()
+		};
+		let start = Instant::now();
+		for _ in 0..benchmark.iterations {
+			let mut file = File::create(&path).expect("benchmark output file should be writable");
+			execute(bytecode, &mut &benchmark.input[..], &mut file, &config)
+				.expect("benchmark should run");
+		}
+		start.elapsed().as_secs_f64() / f64::from(benchmark.iterations)
+	};
+	let line = time(BufferMode::Line);
+	let full = time(BufferMode::Full);
+	let _ = fs::remove_file(&path);
+	println!(
+		"{:<24} {:>10.4} s line buffered {:>10.4} s fully buffered {:>8.2}x",
+		benchmark.name,
+		line,
+		full,
+		line / full
+	);
+}
+
+fn run(benchmark: &Benchmark) {
+	let config = &Config {
+		cell_width: benchmark.cell_width,
+		tape_mode: benchmark.tape_mode,
+		preallocate_tape: benchmark.preallocate_tape,
+		..Config::default()
+	};
+	let bytecode = compile(String::from(benchmark.source_code)).expect("benchmark should compile");
+
+	let mut output = Vec::new();
+	let profile = profile(&bytecode, &mut &benchmark.input[..], &mut output, config)
+		.expect("benchmark should run");
+	assert_eq!(
+		output, benchmark.expected_output,
+		"{} produced the wrong output",
+		benchmark.name
+	);
+
+	let start = Instant::now();
+	for _ in 0..benchmark.iterations {
+		execute(
+			&bytecode,
+			&mut &benchmark.input[..],
+			&mut io::sink(),
+			config,
+		)
+		.expect("benchmark should run");
+	}
+	let seconds = start.elapsed().as_secs_f64() / f64::from(benchmark.iterations);
+	println!(
+		"{:<24} {:>14} instructions {:>10.4} s {:>16.0} instructions/s",
+		benchmark.name,
+		profile.total_steps,
+		seconds,
+		profile.total_steps as f64 / seconds
+	);
+	if benchmark.compare_tapes {
+		compare_tapes(benchmark, bytecode.instructions());
+	}
+	if benchmark.compare_buffering {
+		compare_buffering(benchmark, &bytecode, config);
+	}
+}
+
+fn main() {
+	let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+	for benchmark in BENCHMARKS {
+		if filter
+			.as_deref()
+			.is_none_or(|filter| benchmark.name.contains(filter))
+		{
+			run(benchmark);
+		}
+	}
+}
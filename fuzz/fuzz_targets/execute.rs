@@ -0,0 +1,33 @@
+#![no_main]
+
+use brainfuck::{
+	compile_with_config, execute, execute_bounded, Config, OptLevel, RuntimeErrorKind,
+};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_STEPS: u64 = 100_000;
+
+// The data is the program followed by a zero byte and its input.
+fuzz_target!(|data: &[u8]| {
+	let (source, input) = match data.iter().position(|&byte| byte == 0) {
+		Some(separator) => (&data[..separator], &data[separator + 1..]),
+		None => (data, &[][..]),
+	};
+	let Ok(optimized) = execute_bounded(source, input, MAX_STEPS) else {
+		return;
+	};
+	// The unoptimized program executes more steps, so it gets a generous limit of its own. A
+	// divergence that makes it loop forever would otherwise show up as a timeout.
+	let config = Config {
+		opt_level: OptLevel::None,
+		max_steps: Some(MAX_STEPS * 256),
+		..Config::default()
+	};
+	let bytecode = compile_with_config(source, &config).expect("the optimized program compiled");
+	let mut unoptimized = Vec::new();
+	match execute(&bytecode, &mut &input[..], &mut unoptimized, &config) {
+		Err(error) if matches!(error.kind, RuntimeErrorKind::StepLimitExceeded) => return,
+		result => result.expect("the optimized program ran"),
+	}
+	assert_eq!(optimized, unoptimized);
+});
@@ -0,0 +1,5 @@
+Walks one million cells to the left carrying a counter along
+
+++++++++++[>++++++++++<-]>[>++++++++++<-]>[>++++++++++<-]>[>++++++++++<-]>[>++++++++++<-]>
+[[-<+>]<-]
+++++++++++.
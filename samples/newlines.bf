@@ -0,0 +1,3 @@
+Prints 65536 newlines from four nested counters of 16
+
+++++++++++>++++++++++++++++[>++++++++++++++++[>++++++++++++++++[>++++++++++++++++[<<<<.>>>>-]<-]<-]<-]
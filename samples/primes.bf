@@ -0,0 +1,25 @@
+Prints all primes up to 250 using trial division
+
+++>+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++[>>>[-]+<<[-]++>[-]>>>>[-]<<<<<<<[->>>+>>>>+<<<<<<<]>>>>>>>[-<<<<<<<
++>>>>>>>]<<<<--[>>>>>>[-]>[-]>[-]>[-]>[-]>[-]<<<<<[-]<<[-]<<<<<<<[->>>>>>>>>+<<+
+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]>>>>[-]<<<<[-]<<<<<[->>>>>>>>>+<<<<+<<<<<]>>>>>
+[-<<<<<+>>>>>]>>[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]<[-]+>>>>[<<<<->>>>[-]]<<<<[<<<
+<[-]>>>>[-]]<<<<<<+>-]>>[-]>>[-]<<<[->+>>+<<<]>>>[-<<<+>>>]<<[>>>>>>>>>>>[-]>[-]
+>[-]>[-]>[-]>[-]>[-]>[-]<<<<<<<<<<<<<<<<<[-]++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++>>>[-]>[-]>[-]>[
+-]>[-]>[-]<<<<<[-]<<[-]<<<<<<<[->>>>>>>>>+<<+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]>>
+>>[-]<<<<[-]<[->>>>>+<<<<+<]>[-<+>]>>[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>>>[->>>+
+<<<]<[->>>>>+<<<<<]<<<<<<[-]++++++++++>>>[-]>[-]>[-]>[-]>[-]>[-]<<<<<[-]<<[-]>>>
+>>>>>>>[-<<<<<<<<+<<+>>>>>>>>>>]<<<<<<<<<<[->>>>>>>>>>+<<<<<<<<<<]>>>>[-]<<<<[-]
+<[->>>>>+<<<<+<]>[-<+>]>>[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>>>[->>>>>+<<<<<]<[->
+>>>>>>+<<<<<<<]>>>>>>>>>[-]<<<<<<<<<<<<<<[-]>>>>>>>>>[->>>>>+<<<<<<<<<<<<<<+>>>>
+>>>>>]<<<<<<<<<[->>>>>>>>>+<<<<<<<<<]>>>>>>>>>>>>>>[<<<<<+++++++++++++++++++++++
++++++++++++++++++++++++++.>>>>[-]+>[-]][-]<<<<<<<<<<<<<<[-]>>>>>>>>>>>[->>>+<<<<
+<<<<<<<<<<+>>>>>>>>>>>]<<<<<<<<<<<[->>>>>>>>>>>+<<<<<<<<<<<]>>>>>>>>>>>>>>>[-]<<
+<<<<<<<<<<<<<[-]>>>>>>>>>>>>>[->>+<<<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<<<<<<<<<<<<[->
+>>>>>>>>>>>>+<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-<+>]<[<<<+++++++++++++++++++++++++++
++++++++++++++++++++++.>>>[-]]<<++++++++++++++++++++++++++++++++++++++++++++++++.
+>>>>[-]++++++++++.<<<<<<<<<<<<<<<<<<[-]]<<<<<+>-]
@@ -0,0 +1,5 @@
+Fills three thousand cells to the right with ones and walks back to the start after each one
+
+>++++++++++[>++++++++++<-]>[<<++++++++++++++++++++++++++++++>>-]<<
+[>>[>]+[<]<-]
+>>+++++++++.
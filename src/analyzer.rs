@@ -0,0 +1,50 @@
+use crate::compiler::{Bytecode, Instruction};
+
+/// Works out the lowest and highest cell a program can touch, relative to the starting cell,
+/// for sizing a fixed tape.
+///
+/// This is only decidable when every loop leaves the pointer where it found it, so that the
+/// number of iterations does not matter. Otherwise `None` is returned, as it is for positions
+/// too far out to represent. Code after a halt is still counted, so the range may be wider than
+/// needed.
+///
+/// ```
+/// use brainfuck::{analyze_pointer_range, compile};
+///
+/// let bytecode = compile(String::from(">>><")).unwrap();
+/// assert_eq!(analyze_pointer_range(&bytecode), Some((0, 3)));
+///
+/// let bytecode = compile(String::from("+[<+>>-]")).unwrap();
+/// assert_eq!(analyze_pointer_range(&bytecode), None);
+/// ```
+pub fn analyze_pointer_range(bytecode: &Bytecode) -> Option<(i64, i64)> {
+	let mut position = i64::try_from(bytecode.initial_data_pointer()).ok()?;
+	let last_initial_cell = i64::try_from(bytecode.initial_tape().len()).ok()? - 1;
+	let (mut min, mut max) = (0i64, position.max(last_initial_cell));
+	let mut loop_starts = Vec::new();
+	for instruction in bytecode.instructions() {
+		match *instruction {
+			Instruction::IncPointer => position = position.checked_add(1)?,
+			Instruction::DecPointer => position = position.checked_sub(1)?,
+			Instruction::AddPointer(amount) => {
+				position = position.checked_add(i64::try_from(amount).ok()?)?;
+			}
+			Instruction::SubPointer(amount) => {
+				position = position.checked_sub(i64::try_from(amount).ok()?)?;
+			}
+			Instruction::MulAdd { offset, .. } => {
+				let target = position.checked_add(i64::try_from(offset).ok()?)?;
+				min = min.min(target);
+				max = max.max(target);
+			}
+			Instruction::OpenBracket { .. } => loop_starts.push(position),
+			Instruction::CloseBracket { .. } if loop_starts.pop() != Some(position) => {
+				return None;
+			}
+			_ => {}
+		}
+		min = min.min(position);
+		max = max.max(position);
+	}
+	Some((min, max))
+}
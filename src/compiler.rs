@@ -0,0 +1,581 @@
+use crate::{
+	config::{Config, LeftBoundary, OptLevel, TapeMode},
+	optimizer,
+};
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction {
+	IncPointer,
+	DecPointer,
+	IncByte,
+	DecByte,
+	AddPointer(usize),
+	SubPointer(usize),
+	AddByte(u32),
+	SubByte(u32),
+	SetZero,
+	MulAdd { offset: isize, factor: u32 },
+	Debug,
+	Halt,
+	Output,
+	Input,
+	EmptyOpenBracket,
+	EmptyCloseBracket,
+	OpenBracket { jump_location: usize },
+	CloseBracket { jump_location: usize },
+}
+
+impl Instruction {
+	pub fn mnemonic(&self) -> &'static str {
+		match self {
+			Instruction::IncPointer => "IncPointer",
+			Instruction::DecPointer => "DecPointer",
+			Instruction::IncByte => "IncByte",
+			Instruction::DecByte => "DecByte",
+			Instruction::AddPointer(_) => "AddPointer",
+			Instruction::SubPointer(_) => "SubPointer",
+			Instruction::AddByte(_) => "AddByte",
+			Instruction::SubByte(_) => "SubByte",
+			Instruction::SetZero => "SetZero",
+			Instruction::MulAdd { .. } => "MulAdd",
+			Instruction::Debug => "Debug",
+			Instruction::Halt => "Halt",
+			Instruction::Output => "Output",
+			Instruction::Input => "Input",
+			Instruction::EmptyOpenBracket => "EmptyOpenBracket",
+			Instruction::EmptyCloseBracket => "EmptyCloseBracket",
+			Instruction::OpenBracket { .. } => "OpenBracket",
+			Instruction::CloseBracket { .. } => "CloseBracket",
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bytecode {
+	pub(crate) instructions: Vec<Instruction>,
+	pub(crate) source_offsets: Vec<usize>,
+	pub(crate) line_starts: Vec<usize>,
+	pub(crate) initial_tape: Vec<u32>,
+	pub(crate) initial_data_pointer: usize,
+}
+
+impl Bytecode {
+	pub fn instructions(&self) -> &[Instruction] {
+		&self.instructions
+	}
+
+	pub fn source_offset(&self, index: usize) -> Option<usize> {
+		self.source_offsets.get(index).copied()
+	}
+
+	/// Returns the 1-based line and column in the source code the instruction at `index`
+	/// originated from, counting comments and other non-command characters. Columns count bytes,
+	/// like the source offsets.
+	pub fn source_position(&self, index: usize) -> Option<(usize, usize)> {
+		let offset = self.source_offset(index)?;
+		let line = self
+			.line_starts
+			.partition_point(|&line_start| line_start <= offset);
+		let line_start = self.line_starts.get(line.checked_sub(1)?)?;
+		Some((line, offset - line_start + 1))
+	}
+
+	/// Returns the values added to the cells from the starting cell on before the first
+	/// instruction runs, which is the initial tape of a program started on an empty tape. Only
+	/// programs compiled with [`Config::fold_constants`] have one.
+	pub fn initial_tape(&self) -> &[u32] {
+		&self.initial_tape
+	}
+
+	/// Returns where the data pointer moves to, relative to the starting cell, once the
+	/// [`Bytecode::initial_tape`] is in place.
+	pub fn initial_data_pointer(&self) -> usize {
+		self.initial_data_pointer
+	}
+
+	pub fn len(&self) -> usize {
+		self.instructions.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.instructions.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&Instruction, usize)> {
+		self.instructions
+			.iter()
+			.zip(self.source_offsets.iter().copied())
+	}
+}
+
+impl FromIterator<(Instruction, usize)> for Bytecode {
+	fn from_iter<T: IntoIterator<Item = (Instruction, usize)>>(iter: T) -> Self {
+		let (instructions, source_offsets) = iter.into_iter().unzip();
+		Self {
+			instructions,
+			source_offsets,
+			line_starts: Vec::new(),
+			initial_tape: Vec::new(),
+			initial_data_pointer: 0,
+		}
+	}
+}
+
+impl IntoIterator for Bytecode {
+	type Item = (Instruction, usize);
+	type IntoIter = std::iter::Zip<std::vec::IntoIter<Instruction>, std::vec::IntoIter<usize>>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.instructions.into_iter().zip(self.source_offsets)
+	}
+}
+
+#[derive(Error, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompileErrorKind {
+	#[error("Unbalanced brackets")]
+	UnbalancedBrackets,
+	#[error("Unmatched '[' at line {line}, column {column}")]
+	UnmatchedOpenBracket { line: usize, column: usize },
+	#[error("Unmatched ']' at line {line}, column {column}")]
+	UnmatchedCloseBracket { line: usize, column: usize },
+	#[error("Unexpected character '{character}' at line {line}, column {column}")]
+	UnexpectedCharacter {
+		character: char,
+		line: usize,
+		column: usize,
+	},
+	#[error(
+		"Loop nesting depth {depth} exceeds the limit of {limit} at line {line}, column {column}"
+	)]
+	LoopTooDeep {
+		depth: usize,
+		limit: usize,
+		line: usize,
+		column: usize,
+	},
+}
+
+/// A compile error together with the span of source characters it refers to, if any.
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+#[error("Compile error: {kind}")]
+pub struct CompileError {
+	pub kind: CompileErrorKind,
+	pub span: Option<Range<usize>>,
+}
+
+impl From<CompileErrorKind> for CompileError {
+	fn from(kind: CompileErrorKind) -> Self {
+		CompileError { kind, span: None }
+	}
+}
+
+fn parse_character(character: char, config: &Config) -> Option<Instruction> {
+	match config.char_map.command(character)? {
+		'>' => Some(Instruction::IncPointer),
+		'<' => Some(Instruction::DecPointer),
+		'+' => Some(Instruction::IncByte),
+		'-' => Some(Instruction::DecByte),
+		'.' => Some(Instruction::Output),
+		',' => Some(Instruction::Input),
+		'[' => Some(Instruction::EmptyOpenBracket),
+		']' => Some(Instruction::EmptyCloseBracket),
+		'#' if config.debug => Some(Instruction::Debug),
+		'@' if config.halt => Some(Instruction::Halt),
+		_ => None,
+	}
+}
+
+/// Returns the number of bytes in a leading `#!` line, which is treated as a comment so
+/// scripts can be run directly as executables.
+fn shebang_length(source_code: &[u8]) -> usize {
+	if !source_code.starts_with(b"#!") {
+		return 0;
+	}
+	source_code
+		.iter()
+		.position(|&byte| byte == b'\n')
+		.unwrap_or(source_code.len())
+}
+
+/// Splits source code into its characters with their byte offsets, skipping a leading `#!`
+/// line. Bytes that are not valid UTF-8 come out one at a time as `None`.
+pub(crate) fn characters(source_code: &[u8]) -> impl Iterator<Item = (usize, Option<char>)> + '_ {
+	let start = shebang_length(source_code);
+	let mut chunk_offset = 0;
+	source_code
+		.utf8_chunks()
+		.flat_map(move |chunk| {
+			let (valid, invalid) = (chunk.valid(), chunk.invalid());
+			let offset = chunk_offset;
+			chunk_offset += valid.len() + invalid.len();
+			let characters = valid
+				.char_indices()
+				.map(move |(i, character)| (offset + i, Some(character)));
+			let bytes = (0..invalid.len()).map(move |i| (offset + valid.len() + i, None));
+			characters.chain(bytes)
+		})
+		.skip_while(move |&(offset, _)| offset < start)
+}
+
+/// Returns the byte range taken up by the character or invalid byte at `offset`.
+pub(crate) fn character_span(offset: usize, character: Option<char>) -> Range<usize> {
+	offset..offset + character.map_or(1, char::len_utf8)
+}
+
+fn check_source(source_code: &[u8], config: &Config) -> Result<(), CompileError> {
+	let mut open_positions_stack = Vec::new();
+	let (mut line, mut line_start) = (1, 0);
+	for (offset, character) in characters(source_code) {
+		let column = offset - line_start + 1;
+		let span = Some(character_span(offset, character));
+		match character.and_then(|character| config.char_map.command(character)) {
+			Some('[') => {
+				open_positions_stack.push((span.clone(), line, column));
+				let depth = open_positions_stack.len();
+				if let Some(limit) = config.max_loop_depth.filter(|&limit| depth > limit) {
+					return Err(CompileError {
+						kind: CompileErrorKind::LoopTooDeep {
+							depth,
+							limit,
+							line,
+							column,
+						},
+						span,
+					});
+				}
+			}
+			Some(']') => {
+				open_positions_stack.pop().ok_or(CompileError {
+					kind: CompileErrorKind::UnmatchedCloseBracket { line, column },
+					span,
+				})?;
+			}
+			_ if config.strict && !is_allowed(character, config) => {
+				return Err(CompileError {
+					kind: CompileErrorKind::UnexpectedCharacter {
+						character: character.unwrap_or(char::REPLACEMENT_CHARACTER),
+						line,
+						column,
+					},
+					span,
+				});
+			}
+			_ => (),
+		}
+		if character == Some('\n') {
+			(line, line_start) = (line + 1, offset + 1);
+		}
+	}
+	match open_positions_stack.pop() {
+		Some((span, line, column)) => Err(CompileError {
+			kind: CompileErrorKind::UnmatchedOpenBracket { line, column },
+			span,
+		}),
+		None => Ok(()),
+	}
+}
+
+/// Whether strict mode accepts `character`, which is whitespace or a command. Bytes that are not
+/// valid UTF-8 are never accepted.
+pub(crate) fn is_allowed(character: Option<char>, config: &Config) -> bool {
+	character.is_some_and(|character| {
+		character.is_whitespace() || parse_character(character, config).is_some()
+	})
+}
+
+fn line_starts(source_code: &[u8]) -> Vec<usize> {
+	std::iter::once(0)
+		.chain(
+			source_code
+				.iter()
+				.enumerate()
+				.filter(|&(_, &byte)| byte == b'\n')
+				.map(|(offset, _)| offset + 1),
+		)
+		.collect()
+}
+
+pub(crate) fn is_open_bracket(instruction: &Instruction) -> bool {
+	matches!(
+		instruction,
+		Instruction::EmptyOpenBracket | Instruction::OpenBracket { .. }
+	)
+}
+
+pub(crate) fn is_close_bracket(instruction: &Instruction) -> bool {
+	matches!(
+		instruction,
+		Instruction::EmptyCloseBracket | Instruction::CloseBracket { .. }
+	)
+}
+
+/// Returns the index of the first bracket whose jump location does not point back at it.
+pub(crate) fn invalid_jump_location(bytecode: &Bytecode) -> Option<usize> {
+	bytecode
+		.instructions
+		.iter()
+		.enumerate()
+		.position(|(i, instruction)| match *instruction {
+			Instruction::OpenBracket { jump_location } => !matches!(
+				bytecode.instructions.get(jump_location),
+				Some(Instruction::CloseBracket { jump_location }) if *jump_location == i
+			),
+			Instruction::CloseBracket { jump_location } => !matches!(
+				bytecode.instructions.get(jump_location),
+				Some(Instruction::OpenBracket { jump_location }) if *jump_location == i
+			),
+			Instruction::EmptyOpenBracket | Instruction::EmptyCloseBracket => true,
+			_ => false,
+		})
+}
+
+/// Pairs up the brackets in `instructions`, mapping the index of each bracket to the index of
+/// its partner and every other instruction to `None`.
+///
+/// ```
+/// use brainfuck::{compile, jump_table};
+///
+/// let bytecode = compile(String::from("+[>[<]<]")).unwrap();
+/// assert_eq!(
+///     jump_table(bytecode.instructions()).unwrap(),
+///     [None, Some(7), None, Some(5), None, Some(3), None, Some(1)]
+/// );
+/// ```
+pub fn jump_table(instructions: &[Instruction]) -> Result<Vec<Option<usize>>, CompileError> {
+	let mut table = vec![None; instructions.len()];
+	let mut open_locations_stack = Vec::new();
+	for (i, instruction) in instructions.iter().enumerate() {
+		if is_open_bracket(instruction) {
+			open_locations_stack.push(i);
+		} else if is_close_bracket(instruction) {
+			let open_location = open_locations_stack
+				.pop()
+				.ok_or(CompileErrorKind::UnbalancedBrackets)?;
+			table[i] = Some(open_location);
+			table[open_location] = Some(i);
+		}
+	}
+	if !open_locations_stack.is_empty() {
+		return Err(CompileErrorKind::UnbalancedBrackets.into());
+	}
+	Ok(table)
+}
+
+fn match_brackets(mut bytecode: Bytecode) -> Result<Bytecode, CompileError> {
+	let table = jump_table(&bytecode.instructions)?;
+	for (instruction, jump_location) in bytecode.instructions.iter_mut().zip(table) {
+		if let Some(jump_location) = jump_location {
+			*instruction = if is_open_bracket(instruction) {
+				Instruction::OpenBracket { jump_location }
+			} else {
+				Instruction::CloseBracket { jump_location }
+			};
+		}
+	}
+	Ok(bytecode)
+}
+
+/// Compiles Brainfuck source code into bytecode, ignoring non-command characters.
+///
+/// Each instruction remembers the byte offset in the source code it originated from. A first
+/// line starting with `#!` is skipped, so scripts can start with a shebang line. Source bytes
+/// that are not valid UTF-8 are treated as comments.
+///
+/// Runs of identical pointer and byte operations are folded into single counted instructions,
+/// clear loops like `[-]` are replaced by a single `SetZero`, copy and multiply loops like
+/// `[->++<]` are replaced by `MulAdd` instructions, and loops that can never be entered, such as
+/// a comment loop right after another loop, are removed.
+///
+/// ```
+/// use brainfuck::{compile, CompileErrorKind, Instruction};
+///
+/// let bytecode = compile(String::from("+[>] a comment")).unwrap();
+/// assert_eq!(bytecode.len(), 4);
+/// assert!(matches!(
+///     bytecode.instructions()[1],
+///     Instruction::OpenBracket { jump_location: 3 }
+/// ));
+/// assert_eq!(bytecode.source_offset(3), Some(3));
+///
+/// let bytecode = compile(String::from("Add one\n+ and output it\n  .")).unwrap();
+/// assert_eq!(bytecode.source_position(1), Some((3, 3)));
+///
+/// let bytecode = compile(String::from("#!/usr/bin/env -S brainfuck\n+.")).unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::AddByte(1), Instruction::Output]);
+/// assert_eq!(bytecode.source_position(0), Some((2, 1)));
+///
+/// let bytecode = compile(String::from("+++++")).unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::AddByte(5)]);
+/// let bytecode = compile(String::from("[-]")).unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::SetZero]);
+/// let bytecode = compile(String::from("+[-][ a comment. ]++")).unwrap();
+/// assert_eq!(
+///     bytecode.instructions(),
+///     [Instruction::AddByte(1), Instruction::SetZero, Instruction::AddByte(2)]
+/// );
+/// let bytecode = compile(String::from(">[->++<]")).unwrap();
+/// assert_eq!(
+///     bytecode.instructions(),
+///     [
+///         Instruction::AddPointer(1),
+///         Instruction::MulAdd { offset: 1, factor: 2 },
+///         Instruction::SetZero
+///     ]
+/// );
+///
+/// let bytecode = compile(b"+ \xff binary comment \xfe .").unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::AddByte(1), Instruction::Output]);
+///
+/// let error = compile(String::from("+\n [")).unwrap_err();
+/// assert_eq!(
+///     error.kind,
+///     CompileErrorKind::UnmatchedOpenBracket { line: 2, column: 2 }
+/// );
+/// assert_eq!(error.span, Some(3..4));
+/// assert_eq!(error.to_string(), "Compile error: Unmatched '[' at line 2, column 2");
+/// ```
+pub fn compile(source_code: impl AsRef<[u8]>) -> Result<Bytecode, CompileError> {
+	compile_with_config(source_code, &Config::default())
+}
+
+/// Compiles borrowed Brainfuck source code like [`compile`].
+///
+/// ```
+/// use brainfuck::{compile_str, Instruction};
+///
+/// let bytecode = compile_str("+.").unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::AddByte(1), Instruction::Output]);
+/// ```
+pub fn compile_str(source_code: &str) -> Result<Bytecode, CompileError> {
+	compile(source_code)
+}
+
+/// Compiles Brainfuck source code like [`compile`], honoring the dialect options in `config`.
+///
+/// ```
+/// use brainfuck::{compile_with_config, execute, CompileErrorKind, Config, Instruction};
+///
+/// let config = Config {
+///     debug: true,
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config(String::from("+#@"), &config).unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::AddByte(1), Instruction::Debug]);
+///
+/// let config = Config {
+///     halt: true,
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config(String::from("+.@+."), &config).unwrap();
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &b""[..], &mut output, &config).unwrap();
+/// assert_eq!(output, [1]);
+///
+/// let config = Config {
+///     strict: true,
+///     ..Config::default()
+/// };
+/// let error = compile_with_config(String::from("+ +\n x"), &config).unwrap_err();
+/// assert_eq!(
+///     error.kind,
+///     CompileErrorKind::UnexpectedCharacter {
+///         character: 'x',
+///         line: 2,
+///         column: 2
+///     }
+/// );
+/// assert!(compile_with_config(String::from("+ +\n x"), &Config::default()).is_ok());
+/// ```
+pub fn compile_with_config(
+	source_code: impl AsRef<[u8]>,
+	config: &Config,
+) -> Result<Bytecode, CompileError> {
+	compile_with_stages(source_code, config).map(|(bytecode, _)| bytecode)
+}
+
+/// Measures how densely a program compiles, returning the number of characters in the source,
+/// the number of instructions they parse to and the number left after optimization.
+///
+/// ```
+/// use brainfuck::{code_density, Config};
+///
+/// let density = code_density("Add 12: ++++ ++++ ++++ [-]", &Config::default()).unwrap();
+/// assert_eq!(density, (26, 15, 2));
+/// ```
+pub fn code_density(
+	source_code: &str,
+	config: &Config,
+) -> Result<(usize, usize, usize), CompileError> {
+	let (bytecode, stages) = compile_with_stages(source_code, config)?;
+	Ok((source_code.chars().count(), stages[0].1, bytecode.len()))
+}
+
+type Stages = Vec<(&'static str, usize)>;
+type Pass = fn(Bytecode) -> Bytecode;
+
+/// Compiles Brainfuck source code like [`compile_with_config`], also returning the name of each
+/// compilation stage together with the number of instructions left after it.
+///
+/// ```
+/// use brainfuck::{compile_with_stages, Config};
+///
+/// let (_, stages) = compile_with_stages(String::from("++[-][>]"), &Config::default()).unwrap();
+/// assert_eq!(
+///     stages,
+///     [
+///         ("parse", 8),
+///         ("match_brackets", 8),
+///         ("fold_runs", 7),
+///         ("clear_loops", 5),
+///         ("multiply_loops", 5),
+///         ("dead_loops", 2)
+///     ]
+/// );
+/// ```
+pub fn compile_with_stages(
+	source_code: impl AsRef<[u8]>,
+	config: &Config,
+) -> Result<(Bytecode, Stages), CompileError> {
+	let source_code = source_code.as_ref();
+	check_source(source_code, config)?;
+	let bytecode: Bytecode = characters(source_code)
+		.filter_map(|(offset, character)| Some((parse_character(character?, config)?, offset)))
+		.collect();
+	let mut stages = vec![("parse", bytecode.len())];
+	let mut bytecode = match_brackets(bytecode)?;
+	stages.push(("match_brackets", bytecode.len()));
+	if config.opt_level != OptLevel::None {
+		let mut passes: Vec<(&'static str, Pass)> = vec![
+			("fold_runs", optimizer::fold_runs),
+			("clear_loops", optimizer::clear_loops),
+		];
+		// A clamped pointer or a wrapping tape can make a loop's target cell land somewhere else
+		// than at its offset, even on the loop cell itself.
+		if config.left_boundary != LeftBoundary::Clamp
+			&& !matches!(config.tape_mode, TapeMode::Wrapping(_))
+		{
+			passes.push(("multiply_loops", optimizer::multiply_loops));
+		}
+		passes.push(("dead_loops", optimizer::dead_loops));
+		// Later passes would drop the initial tape when they rebuild the bytecode. Only the final
+		// position of the folded code is kept, so a limited tape could no longer catch it moving
+		// past the end on the way there.
+		if config.fold_constants
+			&& !matches!(config.tape_mode, TapeMode::Fixed(_))
+			&& config.max_tape_cells.is_none()
+		{
+			passes.push(("fold_constants", optimizer::fold_constants));
+		}
+		// Passes remove and merge instructions, so the jump locations they leave behind are
+		// stale until the brackets are matched again.
+		for (name, pass) in passes {
+			bytecode = match_brackets(pass(bytecode))?;
+			stages.push((name, bytecode.len()));
+		}
+	}
+	debug_assert_eq!(invalid_jump_location(&bytecode), None);
+	bytecode.line_starts = line_starts(source_code);
+	Ok((bytecode, stages))
+}
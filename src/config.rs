@@ -0,0 +1,390 @@
+use std::{
+	collections::HashMap,
+	sync::{atomic::AtomicBool, Arc},
+	time::Duration,
+};
+
+/// What the `,` command does once the input is exhausted.
+///
+/// `Error` stops the program with an end of input error, which is reported separately from
+/// failures of the reader itself.
+///
+/// ```
+/// use brainfuck::{compile, execute, Config, EofMode, RuntimeErrorKind};
+/// use std::io::{self, Read};
+///
+/// let bytecode = compile(String::from(",,")).unwrap();
+/// let config = Config {
+///     eof_mode: EofMode::Error,
+///     ..Config::default()
+/// };
+/// let error = execute(&bytecode, &mut &b"A"[..], &mut Vec::new(), &config).unwrap_err();
+/// assert!(matches!(error.kind, RuntimeErrorKind::EndOfInput { bytes_read: 1 }));
+/// assert_eq!(
+///     error.to_string(),
+///     "Runtime error: Unexpected end of input after 1 bytes at offset 1"
+/// );
+///
+/// struct Broken;
+///
+/// impl Read for Broken {
+///     fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+///         Err(io::Error::other("broken pipe"))
+///     }
+/// }
+///
+/// let error = execute(&bytecode, &mut Broken, &mut Vec::new(), &config).unwrap_err();
+/// assert!(matches!(error.kind, RuntimeErrorKind::InputFailed { bytes_read: 0, .. }));
+/// assert_eq!(
+///     error.to_string(),
+///     "Runtime error: Input failed after 0 bytes: broken pipe at offset 0"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EofMode {
+	#[default]
+	Unchanged,
+	Zero,
+	NegativeOne,
+	Error,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TapeMode {
+	#[default]
+	Growable,
+	Fixed(usize),
+	Wrapping(usize),
+}
+
+/// What moving left of the starting cell does on a growable tape.
+///
+/// `Grow` extends the tape to the left without bound, `Error` stops the program like a classic
+/// tape that starts at cell 0, and `Clamp` keeps the pointer on the starting cell. Fixed and
+/// wrapping tapes have their own boundaries and ignore this setting.
+///
+/// The compiler does not turn loops into multiplications under `Clamp`, since clamping the
+/// pointer inside such a loop changes which cells it touches.
+///
+/// ```
+/// use brainfuck::{compile, Config, LeftBoundary, Machine};
+///
+/// let bytecode = compile(String::from("+<+")).unwrap();
+/// let config = Config {
+///     left_boundary: LeftBoundary::Clamp,
+///     ..Config::default()
+/// };
+/// let (mut input, mut output) = (&b""[..], Vec::new());
+/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+/// machine.run().unwrap();
+/// assert_eq!(machine.memory(), &[2]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LeftBoundary {
+	#[default]
+	Grow,
+	Error,
+	Clamp,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellWidth {
+	#[default]
+	U8,
+	U16,
+	U32,
+}
+
+impl CellWidth {
+	pub fn max_value(self) -> u32 {
+		match self {
+			CellWidth::U8 => u8::MAX.into(),
+			CellWidth::U16 => u16::MAX.into(),
+			CellWidth::U32 => u32::MAX,
+		}
+	}
+
+	/// Interprets a cell value as a two's complement number of this width.
+	///
+	/// Cells are always stored unsigned, and wrapping addition and subtraction give the same bits
+	/// for signed and unsigned cells, so signedness only matters when a value is shown as a number.
+	///
+	/// ```
+	/// use brainfuck::CellWidth;
+	///
+	/// assert_eq!(CellWidth::U8.to_signed(127), 127);
+	/// assert_eq!(CellWidth::U8.to_signed(128), -128);
+	/// assert_eq!(CellWidth::U16.to_signed(65535), -1);
+	/// ```
+	pub fn to_signed(self, value: u32) -> i64 {
+		let sign_bit = (self.max_value() >> 1) + 1;
+		if value & sign_bit == 0 {
+			value.into()
+		} else {
+			i64::from(value) - i64::from(self.max_value()) - 1
+		}
+	}
+}
+
+/// How the `.` command writes the current cell.
+///
+/// `Decimal` writes the cell as a number followed by a space, negative if `signed_cells` is set
+/// in the [`Config`]. `HexDump` writes each byte as two hex digits, 16 to a line after the offset
+/// of the line's first byte, for programs that generate binary data.
+///
+/// ```
+/// use brainfuck::{compile, execute, Config, InputMode, OutputMode};
+///
+/// let bytecode = compile(String::from(",.+.,.-.")).unwrap();
+/// let config = Config {
+///     input_mode: InputMode::Decimal,
+///     output_mode: OutputMode::Decimal,
+///     ..Config::default()
+/// };
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &b"127 0"[..], &mut output, &config).unwrap();
+/// assert_eq!(output, b"127 128 0 255 ");
+///
+/// let config = Config {
+///     signed_cells: true,
+///     ..config
+/// };
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &b"127 0"[..], &mut output, &config).unwrap();
+/// assert_eq!(output, b"127 -128 0 -1 ");
+///
+/// let bytecode = compile(String::from(",.,.,.")).unwrap();
+/// let config = Config {
+///     output_mode: OutputMode::HexDump,
+///     ..Config::default()
+/// };
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &[0x00, 0x41, 0xff][..], &mut output, &config).unwrap();
+/// assert_eq!(output, b"00000000: 00 41 ff\n");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+	#[default]
+	Bytes,
+	Decimal,
+	HexDump,
+}
+
+/// How the `,` command reads from the input stream.
+///
+/// In `Decimal` mode a whitespace separated integer is read and stored modulo the cell width.
+/// In `Disabled` mode `,` fails with
+/// [`RuntimeErrorKind::InputNotAllowed`](crate::RuntimeErrorKind::InputNotAllowed) instead of
+/// waiting for input, for programs that are expected to be self-contained.
+///
+/// ```
+/// use brainfuck::{compile, Config, InputMode, Machine};
+///
+/// let bytecode = compile(String::from(",")).unwrap();
+/// let config = Config {
+///     input_mode: InputMode::Decimal,
+///     ..Config::default()
+/// };
+/// let (mut input, mut output) = (&b"42\n"[..], Vec::new());
+/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+/// machine.step().unwrap();
+/// assert_eq!(machine.memory()[0], 42);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputMode {
+	#[default]
+	Bytes,
+	Decimal,
+	Disabled,
+}
+
+/// Which characters of the source code are commands and which standard command each one stands
+/// for, so programs of derivatives that use other symbols can be run.
+///
+/// The default map holds the standard commands, each standing for itself. `#` and `@` are still
+/// only commands with `debug` and `halt` set.
+///
+/// ```
+/// use brainfuck::{compile_with_config, execute, CharMap, Config};
+///
+/// let config = Config {
+///     char_map: CharMap::default().with('a', '+'),
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config(String::from("aaa."), &config).unwrap();
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &b""[..], &mut output, &config).unwrap();
+/// assert_eq!(output, [3]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharMap(HashMap<char, char>);
+
+impl CharMap {
+	/// Makes `character` stand for the standard `command`, such as `'+'`.
+	pub fn with(mut self, character: char, command: char) -> Self {
+		self.0.insert(character, command);
+		self
+	}
+
+	/// Returns the standard command `character` stands for, if any.
+	pub fn command(&self, character: char) -> Option<char> {
+		self.0.get(&character).copied()
+	}
+}
+
+impl Default for CharMap {
+	fn default() -> Self {
+		Self(
+			"+-<>[].,#@"
+				.chars()
+				.map(|command| (command, command))
+				.collect(),
+		)
+	}
+}
+
+/// How program output is buffered before it reaches the output stream.
+///
+/// `Line` flushes after every newline, which suits interactive terminals, while `Full` only
+/// flushes before reading input and once the program finishes.
+///
+/// ```
+/// use brainfuck::{compile, execute, BufferMode, Config};
+/// use std::io::{self, Write};
+///
+/// struct Chunks(Vec<Vec<u8>>);
+///
+/// impl Write for Chunks {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.push(buf.to_vec());
+///         Ok(buf.len())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let bytecode = compile(String::from("++++++++++[>++++++++++<-]>---.<++++++++++.>+.")).unwrap();
+/// let config = Config {
+///     buffer_mode: BufferMode::Line,
+///     ..Config::default()
+/// };
+/// let mut output = Chunks(Vec::new());
+/// execute(&bytecode, &mut &b""[..], &mut output, &config).unwrap();
+/// assert_eq!(output.0.concat(), b"a\nb");
+/// assert_eq!(output.0.last().unwrap(), b"b");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferMode {
+	#[default]
+	Full,
+	Line,
+}
+
+/// Which optimization passes the compiler runs. `None` keeps one instruction per command,
+/// which is useful when debugging the optimizer or comparing against the optimized program.
+///
+/// ```
+/// use brainfuck::{compile_with_config, Config, Instruction, OptLevel};
+///
+/// let config = Config {
+///     opt_level: OptLevel::None,
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config(String::from("++"), &config).unwrap();
+/// assert_eq!(bytecode.instructions(), [Instruction::IncByte, Instruction::IncByte]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptLevel {
+	None,
+	#[default]
+	Full,
+}
+
+/// Dialect and runtime options shared by the compiler and the interpreter.
+///
+/// `max_tape_cells` caps how many cells the tape may grow to, regardless of the tape mode.
+/// A non-zero `trace_length` makes a [`Machine`](crate::Machine) remember that many of its
+/// most recent steps.
+///
+/// `strip_input_newlines` makes `,` skip newline bytes in [`InputMode::Bytes`], so pressing Enter
+/// in an interactive session only sends the line to the program.
+///
+/// `dump_chars` shows printable ASCII cells as characters next to their values in the memory
+/// dumps of `#` and [`Machine::dump_memory`](crate::Machine::dump_memory).
+///
+/// `initial_cell_value` is the value every cell holds before the program first writes to it,
+/// zero in most dialects.
+///
+/// `preallocate_tape` allocates the whole tape of a [`TapeMode::Fixed`] tape before the program
+/// starts, so it never grows during the run and every cell is addressable right away.
+///
+/// `fold_constants` makes the compiler work out the cells set by the `+`, `-`, `>` and `<` at the
+/// start of a program and turn them into the bytecode's
+/// [`initial_tape`](crate::Bytecode::initial_tape), up to the first loop, I/O or move left of the
+/// starting cell. The folded code no longer runs as instructions, so it does not count towards
+/// `max_steps` and does not show up in profiles or traces. It is ignored with [`OptLevel::None`],
+/// which leaves every command as its own instruction, and on a [`TapeMode::Fixed`] tape or with
+/// `max_tape_cells`, where the folded moves could run past the end of the tape.
+///
+/// `max_loop_depth` makes the compiler reject programs whose brackets nest deeper than the
+/// limit, a cheap guard when running untrusted code.
+///
+/// `log_output` writes a line like `[cell 3] = 65 -> 'A'` for every `.` to stderr, or to the
+/// error output of a [`Machine`](crate::Machine), to tell which cell printed which byte.
+///
+/// `max_output` stops the program with an output limit error when it executes `.` after having
+/// printed that many cells, which are bytes in [`OutputMode::Bytes`].
+///
+/// `timeout` limits the wall-clock time of a run. Reading the clock on every step would slow
+/// down the interpreter noticeably, so the elapsed time is only checked every
+/// [`TIMEOUT_CHECK_INTERVAL`](crate::TIMEOUT_CHECK_INTERVAL) steps. A run can therefore overshoot
+/// the timeout by the time those steps take, and a program blocked on input is not interrupted.
+///
+/// Setting the `interrupt` flag, for example from a Ctrl-C handler, stops the program with an
+/// interrupted error. It is checked at the same interval as the timeout.
+///
+/// ```
+/// use brainfuck::{compile, execute, Config, RuntimeErrorKind};
+///
+/// let bytecode = compile(String::from("+[>+]")).unwrap();
+/// let config = Config {
+///     max_tape_cells: Some(100),
+///     ..Config::default()
+/// };
+/// let error = execute(&bytecode, &mut &b""[..], &mut Vec::new(), &config).unwrap_err();
+/// assert!(matches!(error.kind, RuntimeErrorKind::TapeLimitExceeded));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+	pub eof_mode: EofMode,
+	pub tape_mode: TapeMode,
+	pub preallocate_tape: bool,
+	pub left_boundary: LeftBoundary,
+	pub cell_width: CellWidth,
+	pub initial_cell_value: u8,
+	pub output_mode: OutputMode,
+	pub signed_cells: bool,
+	pub input_mode: InputMode,
+	pub strip_input_newlines: bool,
+	pub buffer_mode: BufferMode,
+	pub max_steps: Option<u64>,
+	pub max_tape_cells: Option<usize>,
+	pub max_output: Option<usize>,
+	pub timeout: Option<Duration>,
+	/// Stops the run with an interrupted error once set. The flag is only polled every
+	/// [`TIMEOUT_CHECK_INTERVAL`](crate::TIMEOUT_CHECK_INTERVAL) (65536) steps, so a run keeps
+	/// going for up to that many steps after it is set.
+	pub interrupt: Option<Arc<AtomicBool>>,
+	pub trace_length: usize,
+	pub dump_chars: bool,
+	pub log_output: bool,
+	pub opt_level: OptLevel,
+	pub debug: bool,
+	pub halt: bool,
+	pub strict: bool,
+	pub char_map: CharMap,
+	pub max_loop_depth: Option<usize>,
+	pub fold_constants: bool,
+}
@@ -0,0 +1,208 @@
+use crate::{
+	compiler::{invalid_jump_location, Bytecode, Instruction},
+	profiler::Profile,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AssembleError {
+	#[error("Listing error: Invalid instruction on line {0}")]
+	InvalidLine(usize),
+	#[error("Listing error: Invalid jump location at instruction {0}")]
+	InvalidJumpLocation(usize),
+}
+
+fn operand(instruction: &Instruction) -> String {
+	match *instruction {
+		Instruction::AddPointer(amount) | Instruction::SubPointer(amount) => format!(" {}", amount),
+		Instruction::AddByte(amount) | Instruction::SubByte(amount) => format!(" {}", amount),
+		Instruction::MulAdd { offset, factor } => format!(" {} {}", offset, factor),
+		Instruction::OpenBracket { jump_location }
+		| Instruction::CloseBracket { jump_location } => {
+			format!(" -> {:04}", jump_location)
+		}
+		_ => String::new(),
+	}
+}
+
+/// Lists the cells of a non-empty initial tape and where the data pointer starts, or `None`.
+fn tape_line(bytecode: &Bytecode) -> Option<String> {
+	if bytecode.initial_tape.is_empty() && bytecode.initial_data_pointer == 0 {
+		return None;
+	}
+	let cells: String = bytecode
+		.initial_tape
+		.iter()
+		.map(|cell| format!(" {}", cell))
+		.collect();
+	Some(format!(
+		"Tape{} -> {}\n",
+		cells, bytecode.initial_data_pointer
+	))
+}
+
+fn instruction_line(i: usize, instruction: &Instruction) -> String {
+	format!(
+		"{:04} {}{}\n",
+		i,
+		instruction.mnemonic(),
+		operand(instruction)
+	)
+}
+
+/// Lists each instruction with its index, operand and, for brackets, its jump target. A program
+/// with an initial tape starts with a `Tape` line listing its cells and the data pointer.
+///
+/// ```
+/// use brainfuck::{compile, compile_with_config, disassemble, Config};
+///
+/// let listing = disassemble(&compile(String::from("+[>]")).unwrap());
+/// assert_eq!(
+///     listing,
+///     "0000 AddByte 1\n0001 OpenBracket -> 0003\n0002 AddPointer 1\n0003 CloseBracket -> 0001\n"
+/// );
+///
+/// let config = Config {
+///     fold_constants: true,
+///     ..Config::default()
+/// };
+/// let listing = disassemble(&compile_with_config("+>>++.", &config).unwrap());
+/// assert_eq!(listing, "Tape 1 0 2 -> 2\n0000 Output\n");
+/// ```
+pub fn disassemble(bytecode: &Bytecode) -> String {
+	let instructions = bytecode
+		.instructions()
+		.iter()
+		.enumerate()
+		.map(|(i, instruction)| instruction_line(i, instruction));
+	tape_line(bytecode)
+		.into_iter()
+		.chain(instructions)
+		.collect()
+}
+
+/// Lists each instruction like [`disassemble`], prefixed with how many times it was executed in
+/// `profile`, to find the instructions a program spends its time in. The initial tape is set up
+/// once.
+///
+/// ```
+/// use brainfuck::{compile, disassemble_hot, profile, Config};
+///
+/// let bytecode = compile(String::from("++[.-]")).unwrap();
+/// let profile = profile(&bytecode, &mut &b""[..], &mut Vec::new(), &Config::default()).unwrap();
+/// assert_eq!(
+///     disassemble_hot(&bytecode, &profile),
+///     concat!(
+///         "         1 0000 AddByte 2\n",
+///         "         2 0001 OpenBracket -> 0004\n",
+///         "         2 0002 Output\n",
+///         "         2 0003 SubByte 1\n",
+///         "         2 0004 CloseBracket -> 0001\n",
+///     )
+/// );
+/// ```
+pub fn disassemble_hot(bytecode: &Bytecode, profile: &Profile) -> String {
+	let tape = tape_line(bytecode).map(|line| format!("{:>10} {}", 1, line));
+	let instructions = bytecode
+		.instructions()
+		.iter()
+		.enumerate()
+		.map(|(i, instruction)| {
+			let count = profile.execution_counts.get(i).copied().unwrap_or(0);
+			format!("{:>10} {}", count, instruction_line(i, instruction))
+		});
+	tape.into_iter().chain(instructions).collect()
+}
+
+fn parse_tape(operands: &[&str]) -> Option<(Vec<u32>, usize)> {
+	let [cells @ .., "->", data_pointer] = operands else {
+		return None;
+	};
+	let cells = cells
+		.iter()
+		.map(|cell| cell.parse().ok())
+		.collect::<Option<_>>()?;
+	Some((cells, data_pointer.parse().ok()?))
+}
+
+fn parse_instruction(mnemonic: &str, operands: &[&str]) -> Option<Instruction> {
+	Some(match (mnemonic, operands) {
+		("IncPointer", []) => Instruction::IncPointer,
+		("DecPointer", []) => Instruction::DecPointer,
+		("IncByte", []) => Instruction::IncByte,
+		("DecByte", []) => Instruction::DecByte,
+		("AddPointer", [amount]) => Instruction::AddPointer(amount.parse().ok()?),
+		("SubPointer", [amount]) => Instruction::SubPointer(amount.parse().ok()?),
+		("AddByte", [amount]) => Instruction::AddByte(amount.parse().ok()?),
+		("SubByte", [amount]) => Instruction::SubByte(amount.parse().ok()?),
+		("SetZero", []) => Instruction::SetZero,
+		("MulAdd", [offset, factor]) => Instruction::MulAdd {
+			offset: offset.parse().ok()?,
+			factor: factor.parse().ok()?,
+		},
+		("Debug", []) => Instruction::Debug,
+		("Halt", []) => Instruction::Halt,
+		("Output", []) => Instruction::Output,
+		("Input", []) => Instruction::Input,
+		("OpenBracket", ["->", jump_location]) => Instruction::OpenBracket {
+			jump_location: jump_location.parse().ok()?,
+		},
+		("CloseBracket", ["->", jump_location]) => Instruction::CloseBracket {
+			jump_location: jump_location.parse().ok()?,
+		},
+		_ => return None,
+	})
+}
+
+/// Reads a listing in the format produced by [`disassemble`] back into bytecode, so optimized
+/// programs can be edited by hand or generated by other tools.
+///
+/// The indices must count up from zero and every bracket must jump to its partner. Blank lines
+/// are ignored. As the listing has no source code, each instruction's source offset is its index.
+/// A `Tape` line may come before the first instruction.
+///
+/// ```
+/// use brainfuck::{assemble, compile, disassemble, AssembleError};
+///
+/// let bytecode = compile(String::from("++[>+<-]")).unwrap();
+/// let assembled = assemble(&disassemble(&bytecode)).unwrap();
+/// assert_eq!(assembled.instructions(), bytecode.instructions());
+///
+/// let error = assemble("0000 OpenBracket -> 0001\n0001 Output\n").unwrap_err();
+/// assert_eq!(error, AssembleError::InvalidJumpLocation(0));
+/// ```
+pub fn assemble(listing: &str) -> Result<Bytecode, AssembleError> {
+	let mut lines = listing
+		.lines()
+		.enumerate()
+		.filter(|(_, text)| !text.trim().is_empty())
+		.peekable();
+	let tape = lines
+		.next_if(|(_, text)| text.split_whitespace().next() == Some("Tape"))
+		.map(|(line, text)| {
+			let operands: Vec<&str> = text.split_whitespace().skip(1).collect();
+			parse_tape(&operands).ok_or(AssembleError::InvalidLine(line + 1))
+		})
+		.transpose()?;
+	let mut bytecode = lines
+		.enumerate()
+		.map(|(i, (line, text))| {
+			let mut words = text.split_whitespace();
+			let index = words.next().and_then(|index| index.parse::<usize>().ok());
+			let mnemonic = words.next().unwrap_or_default();
+			let operands: Vec<&str> = words.collect();
+			parse_instruction(mnemonic, &operands)
+				.filter(|_| index == Some(i))
+				.map(|instruction| (instruction, i))
+				.ok_or(AssembleError::InvalidLine(line + 1))
+		})
+		.collect::<Result<Bytecode, AssembleError>>()?;
+	if let Some((initial_tape, initial_data_pointer)) = tape {
+		bytecode.initial_tape = initial_tape;
+		bytecode.initial_data_pointer = initial_data_pointer;
+	}
+	match invalid_jump_location(&bytecode) {
+		Some(i) => Err(AssembleError::InvalidJumpLocation(i)),
+		None => Ok(bytecode),
+	}
+}
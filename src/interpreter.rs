@@ -0,0 +1,1305 @@
+use crate::{
+	compiler::{Bytecode, Instruction},
+	config::{
+		BufferMode, CellWidth, Config, EofMode, InputMode, LeftBoundary, OutputMode, TapeMode,
+	},
+};
+use std::{
+	collections::{HashSet, VecDeque},
+	io::{self, BufWriter, LineWriter, Read, Write},
+	iter, mem,
+	sync::atomic::Ordering,
+	time::Instant,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RuntimeErrorKind {
+	#[error("Out of memory")]
+	OutOfMemory,
+	#[error("Pointer out of bounds")]
+	PointerOutOfBounds,
+	#[error("Unexpected end of input after {bytes_read} bytes")]
+	EndOfInput { bytes_read: usize },
+	#[error("Input failed after {bytes_read} bytes: {source}")]
+	InputFailed {
+		bytes_read: usize,
+		#[source]
+		source: io::Error,
+	},
+	#[error("Input requested but none allowed")]
+	InputNotAllowed,
+	#[error("Malformed number in input")]
+	MalformedNumber,
+	#[error("Output failed")]
+	OutputFailed,
+	#[error("Step limit exceeded")]
+	StepLimitExceeded,
+	#[error("Time limit exceeded")]
+	TimeLimitExceeded,
+	#[error("Interrupted")]
+	Interrupted,
+	#[error("Tape limit exceeded")]
+	TapeLimitExceeded,
+	#[error("Output limit exceeded")]
+	OutputLimitExceeded,
+}
+
+#[derive(Error, Debug)]
+#[error("Runtime error: {kind} at offset {offset}")]
+pub struct RuntimeError {
+	pub kind: RuntimeErrorKind,
+	pub offset: usize,
+}
+
+const DUMP_RADIUS: usize = 5;
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// How many steps run between two checks of [`Config::timeout`] and [`Config::interrupt`].
+pub const TIMEOUT_CHECK_INTERVAL: u64 = 1 << 16;
+
+/// Formats a cell for the memory dumps, followed by its character if `dump_chars` is set. Only
+/// printable ASCII is shown as is, anything else as a dot.
+fn format_cell(cell: u32, dump_chars: bool) -> String {
+	match u8::try_from(cell) {
+		_ if !dump_chars => cell.to_string(),
+		Ok(byte) if byte.is_ascii_graphic() || byte == b' ' => {
+			format!("{} '{}'", cell, char::from(byte))
+		}
+		_ => format!("{} .", cell),
+	}
+}
+
+/// The tape is a single `Vec` that grows in both directions by at least doubling, so moving
+/// either way is amortized O(1). `origin` is the index of the starting cell, and only the cells
+/// between `start` and `end` have been visited, all other cells are zero.
+#[derive(Clone)]
+struct State {
+	memory: Vec<u32>,
+	origin: usize,
+	start: usize,
+	end: usize,
+	data_pointer: usize,
+	instruction_pointer: usize,
+	max_tape_cells: Option<usize>,
+	max_output: Option<usize>,
+	left_boundary: LeftBoundary,
+	dump_chars: bool,
+	initial_cell: u32,
+	bytes_read: usize,
+	bytes_written: usize,
+	/// Whether the bytecode's initial tape has been added, which happens once before the first
+	/// instruction runs.
+	initial_tape_added: bool,
+}
+
+impl State {
+	fn new(config: &Config) -> Self {
+		let len = match config.tape_mode {
+			TapeMode::Fixed(size) if config.preallocate_tape => size.max(1),
+			_ => 1,
+		};
+		let initial_cell = u32::from(config.initial_cell_value) & config.cell_width.max_value();
+		Self {
+			memory: vec![initial_cell; len],
+			origin: 0,
+			start: 0,
+			end: len,
+			data_pointer: 0,
+			instruction_pointer: 0,
+			max_tape_cells: config.max_tape_cells,
+			max_output: config.max_output,
+			left_boundary: config.left_boundary,
+			dump_chars: config.dump_chars,
+			initial_cell,
+			bytes_read: 0,
+			bytes_written: 0,
+			initial_tape_added: false,
+		}
+	}
+
+	fn position(&self) -> isize {
+		self.data_pointer as isize - self.origin as isize
+	}
+
+	fn cell(&self, position: isize) -> u32 {
+		self.origin
+			.checked_add_signed(position)
+			.filter(|index| (self.start..self.end).contains(index))
+			.map_or(self.initial_cell, |index| self.memory[index])
+	}
+
+	fn check_tape_limit(&self, cells: usize) -> Result<(), RuntimeErrorKind> {
+		match self.max_tape_cells {
+			Some(max_tape_cells) if cells > max_tape_cells => {
+				Err(RuntimeErrorKind::TapeLimitExceeded)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn extend_right(&mut self, index: usize) -> Result<(), RuntimeErrorKind> {
+		if index >= self.end {
+			self.check_tape_limit(index + 1 - self.start)?;
+			if index >= self.memory.len() {
+				let len = (index + 1).max(self.memory.len() * 2);
+				self.memory
+					.try_reserve_exact(len - self.memory.len())
+					.map_err(|_| RuntimeErrorKind::OutOfMemory)?;
+				self.memory.resize(len, self.initial_cell);
+			}
+			self.end = index + 1;
+		}
+		Ok(())
+	}
+
+	fn extend_left(&mut self, amount: usize) -> Result<(), RuntimeErrorKind> {
+		if amount <= self.data_pointer - self.start {
+			return Ok(());
+		}
+		self.check_tape_limit((self.end - self.data_pointer).saturating_add(amount))?;
+		if amount > self.data_pointer {
+			let extra = (amount - self.data_pointer).max(self.memory.len());
+			self.memory
+				.try_reserve_exact(extra)
+				.map_err(|_| RuntimeErrorKind::OutOfMemory)?;
+			self.memory
+				.splice(0..0, iter::repeat_n(self.initial_cell, extra));
+			self.origin += extra;
+			self.start += extra;
+			self.end += extra;
+			self.data_pointer += extra;
+		}
+		self.start = self.data_pointer - amount;
+		Ok(())
+	}
+
+	/// Grows the tape if the data pointer sits at its frontier, so the current cell is in bounds.
+	fn current_cell(&mut self) -> Result<&mut u32, RuntimeErrorKind> {
+		self.extend_right(self.data_pointer)?;
+		Ok(&mut self.memory[self.data_pointer])
+	}
+
+	/// Adds `cells` to the tape from the data pointer on and leaves the pointer `data_pointer`
+	/// cells to the right, as running the code they were folded from would.
+	fn add_cells(
+		&mut self,
+		cells: &[u32],
+		data_pointer: usize,
+		tape_mode: TapeMode,
+		cell_width: CellWidth,
+	) -> Result<(), RuntimeErrorKind> {
+		let mut position = 0;
+		for (i, &delta) in cells.iter().enumerate() {
+			if delta != 0 {
+				self.move_right(i - position, tape_mode)?;
+				position = i;
+				let cell = self.current_cell()?;
+				*cell = cell.wrapping_add(delta) & cell_width.max_value();
+			}
+		}
+		if data_pointer >= position {
+			self.move_right(data_pointer - position, tape_mode)
+		} else {
+			self.move_left(position - data_pointer, tape_mode)
+		}
+	}
+
+	fn wrap_pointer(&mut self, position: usize) -> Result<(), RuntimeErrorKind> {
+		let data_pointer = self.origin + position;
+		self.extend_right(data_pointer)?;
+		self.data_pointer = data_pointer;
+		Ok(())
+	}
+
+	fn move_right(&mut self, amount: usize, tape_mode: TapeMode) -> Result<(), RuntimeErrorKind> {
+		if let TapeMode::Wrapping(size) = tape_mode {
+			if size == 0 {
+				return Err(RuntimeErrorKind::PointerOutOfBounds);
+			}
+			let position = self.data_pointer - self.origin;
+			return self.wrap_pointer((position + amount % size) % size);
+		}
+		let Some(data_pointer) = self.data_pointer.checked_add(amount) else {
+			return Err(RuntimeErrorKind::OutOfMemory);
+		};
+		if let TapeMode::Fixed(size) = tape_mode {
+			if data_pointer - self.origin >= size {
+				return Err(RuntimeErrorKind::PointerOutOfBounds);
+			}
+		}
+		self.extend_right(data_pointer)?;
+		self.data_pointer = data_pointer;
+		Ok(())
+	}
+
+	fn move_left(&mut self, amount: usize, tape_mode: TapeMode) -> Result<(), RuntimeErrorKind> {
+		if let TapeMode::Wrapping(size) = tape_mode {
+			if size == 0 {
+				return Err(RuntimeErrorKind::PointerOutOfBounds);
+			}
+			let position = self.data_pointer - self.origin;
+			return self.wrap_pointer((position + size - amount % size) % size);
+		}
+		let left_boundary = match tape_mode {
+			TapeMode::Fixed(_) => LeftBoundary::Error,
+			_ => self.left_boundary,
+		};
+		if left_boundary != LeftBoundary::Grow && amount > self.data_pointer - self.origin {
+			if left_boundary == LeftBoundary::Error {
+				return Err(RuntimeErrorKind::PointerOutOfBounds);
+			}
+			self.data_pointer = self.origin;
+			return Ok(());
+		}
+		self.extend_left(amount)?;
+		self.data_pointer -= amount;
+		Ok(())
+	}
+
+	fn add_pointer(&mut self, amount: usize, tape_mode: TapeMode) -> Result<(), RuntimeErrorKind> {
+		self.move_right(amount, tape_mode)?;
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn sub_pointer(&mut self, amount: usize, tape_mode: TapeMode) -> Result<(), RuntimeErrorKind> {
+		self.move_left(amount, tape_mode)?;
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn add_byte(&mut self, amount: u32, cell_width: CellWidth) -> Result<(), RuntimeErrorKind> {
+		self.memory[self.data_pointer] =
+			self.memory[self.data_pointer].wrapping_add(amount) & cell_width.max_value();
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn sub_byte(&mut self, amount: u32, cell_width: CellWidth) -> Result<(), RuntimeErrorKind> {
+		self.memory[self.data_pointer] =
+			self.memory[self.data_pointer].wrapping_sub(amount) & cell_width.max_value();
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn mul_add(
+		&mut self,
+		offset: isize,
+		factor: u32,
+		tape_mode: TapeMode,
+		cell_width: CellWidth,
+	) -> Result<(), RuntimeErrorKind> {
+		let value = self.memory[self.data_pointer];
+		if value != 0 {
+			if offset < 0 {
+				self.move_left(offset.unsigned_abs(), tape_mode)?;
+			} else {
+				self.move_right(offset.unsigned_abs(), tape_mode)?;
+			}
+			let target = &mut self.memory[self.data_pointer];
+			*target = target.wrapping_add(value.wrapping_mul(factor)) & cell_width.max_value();
+			if offset < 0 {
+				self.move_right(offset.unsigned_abs(), tape_mode)?;
+			} else {
+				self.move_left(offset.unsigned_abs(), tape_mode)?;
+			}
+		}
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn set_zero(&mut self) -> Result<(), RuntimeErrorKind> {
+		self.memory[self.data_pointer] = 0;
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn dump(&self) -> String {
+		let start = self
+			.data_pointer
+			.saturating_sub(DUMP_RADIUS)
+			.max(self.start);
+		let end = (self.data_pointer + DUMP_RADIUS + 1).min(self.end);
+		let mut dump = format!("Data pointer: {}\n", self.position());
+		for index in start..end {
+			let marker = if index == self.data_pointer { '>' } else { ' ' };
+			dump.push_str(&format!(
+				"{} {:>5}: {}\n",
+				marker,
+				index as isize - self.origin as isize,
+				format_cell(self.memory[index], self.dump_chars)
+			));
+		}
+		dump
+	}
+
+	fn debug(
+		&mut self,
+		error_output: &mut dyn Write,
+		position: Option<(usize, usize)>,
+	) -> Result<(), RuntimeErrorKind> {
+		let mut dump = self.dump();
+		if let Some((line, column)) = position {
+			dump.insert_str(0, &format!("Debug at line {}, column {}\n", line, column));
+		}
+		error_output
+			.write_all(dump.as_bytes())
+			.map_err(|_| RuntimeErrorKind::OutputFailed)?;
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn halt(&mut self, program_length: usize) -> Result<(), RuntimeErrorKind> {
+		self.instruction_pointer = program_length;
+		Ok(())
+	}
+
+	fn output(
+		&mut self,
+		output: &mut dyn Write,
+		output_mode: OutputMode,
+		cell_width: CellWidth,
+		signed_cells: bool,
+	) -> Result<(), RuntimeErrorKind> {
+		if self
+			.max_output
+			.is_some_and(|max_output| self.bytes_written >= max_output)
+		{
+			return Err(RuntimeErrorKind::OutputLimitExceeded);
+		}
+		let cell = *self.current_cell()?;
+		match output_mode {
+			OutputMode::Bytes => output.write_all(&[cell as u8]),
+			OutputMode::Decimal if signed_cells => {
+				write!(output, "{} ", cell_width.to_signed(cell))
+			}
+			OutputMode::Decimal => write!(output, "{} ", cell),
+			OutputMode::HexDump => self.hex_dump(output, cell as u8),
+		}
+		.map_err(|_| RuntimeErrorKind::OutputFailed)?;
+		self.bytes_written += 1;
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn log_output(&self, error_output: &mut dyn Write) -> Result<(), RuntimeErrorKind> {
+		let cell = self.memory[self.data_pointer];
+		writeln!(
+			error_output,
+			"[cell {}] = {} -> {:?}",
+			self.position(),
+			cell,
+			cell as u8 as char
+		)
+		.map_err(|_| RuntimeErrorKind::OutputFailed)
+	}
+
+	fn hex_dump(&mut self, output: &mut dyn Write, byte: u8) -> io::Result<()> {
+		if self.bytes_written.is_multiple_of(HEX_DUMP_WIDTH) {
+			write!(output, "{:08x}:", self.bytes_written)?;
+		}
+		write!(output, " {:02x}", byte)?;
+		if (self.bytes_written + 1).is_multiple_of(HEX_DUMP_WIDTH) {
+			writeln!(output)?;
+		}
+		Ok(())
+	}
+
+	/// Ends the last line of a hex dump once the program halts.
+	fn finish_output(
+		&mut self,
+		output: &mut dyn Write,
+		output_mode: OutputMode,
+	) -> Result<(), RuntimeErrorKind> {
+		if output_mode == OutputMode::HexDump && !self.bytes_written.is_multiple_of(HEX_DUMP_WIDTH)
+		{
+			writeln!(output).map_err(|_| RuntimeErrorKind::OutputFailed)?;
+		}
+		Ok(())
+	}
+
+	fn read_byte(&mut self, input: &mut dyn Read) -> Result<Option<u8>, RuntimeErrorKind> {
+		let mut byte = [0u8];
+		match input.read_exact(&mut byte) {
+			Ok(()) => {
+				self.bytes_read += 1;
+				Ok(Some(byte[0]))
+			}
+			Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+			Err(source) => Err(RuntimeErrorKind::InputFailed {
+				bytes_read: self.bytes_read,
+				source,
+			}),
+		}
+	}
+
+	/// Reads an optionally signed decimal integer, skipping leading whitespace and
+	/// consuming the single byte that terminates it. Returns `None` at end of input.
+	fn read_number(
+		&mut self,
+		input: &mut dyn Read,
+		cell_width: CellWidth,
+	) -> Result<Option<u32>, RuntimeErrorKind> {
+		let mut byte = self.read_byte(input)?;
+		while byte.is_some_and(|byte| byte.is_ascii_whitespace()) {
+			byte = self.read_byte(input)?;
+		}
+		let Some(first) = byte else {
+			return Ok(None);
+		};
+		let negative = first == b'-';
+		if negative {
+			byte = self.read_byte(input)?;
+		}
+		let mut value = 0u32;
+		let mut digits = 0;
+		while let Some(digit @ b'0'..=b'9') = byte {
+			value = value.wrapping_mul(10).wrapping_add((digit - b'0').into());
+			digits += 1;
+			byte = self.read_byte(input)?;
+		}
+		if digits == 0 || byte.is_some_and(|byte| !byte.is_ascii_whitespace()) {
+			return Err(RuntimeErrorKind::MalformedNumber);
+		}
+		if negative {
+			value = value.wrapping_neg();
+		}
+		Ok(Some(value & cell_width.max_value()))
+	}
+
+	fn input(
+		&mut self,
+		input: &mut dyn Read,
+		input_mode: InputMode,
+		eof_mode: EofMode,
+		cell_width: CellWidth,
+		strip_newlines: bool,
+	) -> Result<(), RuntimeErrorKind> {
+		let value = match input_mode {
+			InputMode::Bytes => loop {
+				match self.read_byte(input)? {
+					Some(b'\n') if strip_newlines => continue,
+					byte => break byte.map(u32::from),
+				}
+			},
+			InputMode::Decimal => self.read_number(input, cell_width)?,
+			InputMode::Disabled => return Err(RuntimeErrorKind::InputNotAllowed),
+		};
+		let cell = self.current_cell()?;
+		match (value, eof_mode) {
+			(Some(value), _) => *cell = value,
+			(None, EofMode::Unchanged) => (),
+			(None, EofMode::Zero) => *cell = 0,
+			(None, EofMode::NegativeOne) => *cell = cell_width.max_value(),
+			(None, EofMode::Error) => {
+				return Err(RuntimeErrorKind::EndOfInput {
+					bytes_read: self.bytes_read,
+				})
+			}
+		}
+		self.instruction_pointer += 1;
+		Ok(())
+	}
+
+	fn open_bracket(&mut self, jump_location: usize) -> Result<(), RuntimeErrorKind> {
+		if self.memory[self.data_pointer] == 0 {
+			self.instruction_pointer = jump_location;
+		} else {
+			self.instruction_pointer += 1;
+		}
+		Ok(())
+	}
+
+	fn close_bracket(&mut self, jump_location: usize) -> Result<(), RuntimeErrorKind> {
+		if self.memory[self.data_pointer] != 0 {
+			self.instruction_pointer = jump_location;
+		} else {
+			self.instruction_pointer += 1;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+	Running,
+	Halted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+	NeedsInput,
+	Output(u8),
+	Halted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakpointOutcome {
+	Breakpoint(usize),
+	Halted,
+}
+
+/// The tape and pointers of a [`Machine`] at some point of its run, see [`Machine::snapshot`].
+#[derive(Clone)]
+pub struct Snapshot {
+	state: State,
+	steps: u64,
+}
+
+pub struct Machine<'a> {
+	bytecode: &'a Bytecode,
+	input: &'a mut dyn Read,
+	output: &'a mut dyn Write,
+	error_output: Option<&'a mut dyn Write>,
+	config: &'a Config,
+	state: State,
+	steps: u64,
+	started: Option<Instant>,
+	trace: VecDeque<(usize, isize)>,
+	pending_output: VecDeque<u8>,
+	input_requested: bool,
+}
+
+impl<'a> Machine<'a> {
+	pub fn new(
+		bytecode: &'a Bytecode,
+		input: &'a mut dyn Read,
+		output: &'a mut dyn Write,
+		config: &'a Config,
+	) -> Self {
+		Self {
+			bytecode,
+			input,
+			output,
+			error_output: None,
+			config,
+			state: State::new(config),
+			steps: 0,
+			started: None,
+			trace: VecDeque::with_capacity(config.trace_length),
+			pending_output: VecDeque::new(),
+			input_requested: false,
+		}
+	}
+
+	/// Seeds the tape with `memory` before the program starts, e.g. to test a fragment of a
+	/// larger program. Values are truncated to the configured cell width.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from(">.")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config)
+	///     .with_memory(vec![1, 5])
+	///     .with_data_pointer(0);
+	/// while !machine.is_halted() {
+	///     machine.step().unwrap();
+	/// }
+	/// assert_eq!(output, [5]);
+	/// ```
+	pub fn with_memory(mut self, memory: Vec<u32>) -> Self {
+		let max_value = self.config.cell_width.max_value();
+		let position = self.state.data_pointer - self.state.origin;
+		self.state.memory = memory.into_iter().map(|cell| cell & max_value).collect();
+		self.state.memory.resize(
+			self.state.memory.len().max(position + 1),
+			self.state.initial_cell,
+		);
+		self.state.origin = 0;
+		self.state.start = 0;
+		self.state.end = self.state.memory.len();
+		self.state.data_pointer = position;
+		self
+	}
+
+	/// Writes the memory dumps of `#` and the lines of [`Config::log_output`] to `error_output`
+	/// instead of stderr.
+	pub fn with_error_output(mut self, error_output: &'a mut dyn Write) -> Self {
+		self.error_output = Some(error_output);
+		self
+	}
+
+	/// Starts the program with the data pointer at `data_pointer`, growing the tape if needed.
+	pub fn with_data_pointer(mut self, data_pointer: usize) -> Self {
+		let data_pointer = self.state.origin + data_pointer;
+		if data_pointer >= self.state.end {
+			self.state
+				.memory
+				.resize(data_pointer + 1, self.state.initial_cell);
+			self.state.end = data_pointer + 1;
+		}
+		self.state.data_pointer = data_pointer;
+		self
+	}
+
+	/// Continues on the tape and data pointer of `snapshot`, which may come from a machine
+	/// running another program, starting at the first instruction. The program's own initial
+	/// tape, if it has one, is added on top of the snapshot's tape, since that tape is the state
+	/// the program starts from.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let (first, second) = (
+	///     compile(String::from(">+++")).unwrap(),
+	///     compile(String::from("[-<+>]<.")).unwrap(),
+	/// );
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&first, &mut input, &mut output, &config);
+	/// machine.run().unwrap();
+	/// let snapshot = machine.snapshot();
+	/// let mut machine = Machine::new(&second, &mut input, &mut output, &config)
+	///     .with_snapshot(snapshot);
+	/// machine.run().unwrap();
+	/// assert_eq!(machine.memory(), &[3, 0]);
+	/// drop(machine);
+	/// assert_eq!(output, [3]);
+	/// ```
+	pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+		self.state = snapshot.state;
+		self.state.instruction_pointer = 0;
+		self.state.initial_tape_added = false;
+		self.steps = 0;
+		self
+	}
+
+	/// Returns the machine to its initial state with an empty tape, so the program can be run
+	/// again on the remaining input without recompiling it.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine, StepOutcome};
+	///
+	/// let bytecode = compile(String::from(",+.")).unwrap();
+	/// let (mut input, mut output, config) = (&b"ab"[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// while machine.step().unwrap() == StepOutcome::Running {}
+	/// machine.reset();
+	/// assert_eq!(machine.memory(), &[0]);
+	/// while machine.step().unwrap() == StepOutcome::Running {}
+	/// assert_eq!(output, b"bc");
+	/// ```
+	pub fn reset(&mut self) {
+		self.state = State::new(self.config);
+		self.steps = 0;
+		self.started = None;
+		self.trace.clear();
+		self.pending_output.clear();
+		self.input_requested = false;
+	}
+
+	/// Captures the tape, data pointer and instruction pointer, so the machine can later go back
+	/// to this point with [`Machine::restore`].
+	///
+	/// Taking a snapshot clones the visited part of the tape, which is cheap for most programs
+	/// but grows with the tape. Input that was read and output that was written since are not
+	/// part of a snapshot and are not undone by restoring it.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from("+>++<-")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// machine.step().unwrap();
+	/// let snapshot = machine.snapshot();
+	/// machine.run().unwrap();
+	/// assert_eq!(machine.memory(), &[0, 2]);
+	/// machine.restore(snapshot);
+	/// assert_eq!(machine.memory(), &[1]);
+	/// assert_eq!(machine.instruction_pointer(), 1);
+	/// ```
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			state: self.state.clone(),
+			steps: self.steps,
+		}
+	}
+
+	/// Returns the machine to the point a [`Snapshot`] was taken at.
+	pub fn restore(&mut self, snapshot: Snapshot) {
+		self.state = snapshot.state;
+		self.steps = snapshot.steps;
+	}
+
+	/// Lists the cells from the first to the last non-zero cell with their indices relative to
+	/// the starting cell.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from("+>++>>+++")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// machine.run().unwrap();
+	/// assert_eq!(machine.dump_memory(), "    0: 1\n    1: 2\n    2: 0\n    3: 3\n");
+	///
+	/// let bytecode = compile(String::from(",>,")).unwrap();
+	/// let (mut input, mut output) = (&b"A\n"[..], Vec::new());
+	/// let config = Config {
+	///     dump_chars: true,
+	///     ..Config::default()
+	/// };
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// machine.run().unwrap();
+	/// assert_eq!(machine.dump_memory(), "    0: 65 'A'\n    1: 10 .\n");
+	/// ```
+	pub fn dump_memory(&self) -> String {
+		let memory = self.memory();
+		let Some(start) = memory.iter().position(|&cell| cell != 0) else {
+			return String::new();
+		};
+		let end = memory.iter().rposition(|&cell| cell != 0).unwrap_or(start);
+		let offset = self.state.start as isize - self.state.origin as isize;
+		(start..=end)
+			.map(|index| {
+				format!(
+					"{:>5}: {}\n",
+					index as isize + offset,
+					format_cell(memory[index], self.config.dump_chars)
+				)
+			})
+			.collect()
+	}
+
+	/// Renders the cells within `radius` of the data pointer side by side, with their indices
+	/// above and a caret below the current cell, for watching a program run.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from("+>++")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// machine.run().unwrap();
+	/// assert_eq!(
+	///     machine.render_tape(1),
+	///     "     0     1     2\n     1     2     0\n           ^\n"
+	/// );
+	/// ```
+	pub fn render_tape(&self, radius: usize) -> String {
+		let position = self.data_pointer();
+		let radius = radius as isize;
+		let positions = position.saturating_sub(radius)..=position.saturating_add(radius);
+		let mut indices = String::new();
+		let mut cells = String::new();
+		let mut marker = String::new();
+		for index in positions {
+			indices.push_str(&format!("{:>6}", index));
+			cells.push_str(&format!("{:>6}", self.cell(index)));
+			marker.push_str(if index == position {
+				"     ^"
+			} else {
+				"      "
+			});
+		}
+		format!("{}\n{}\n{}\n", indices, cells, marker.trim_end())
+	}
+
+	/// Returns the `(instruction_pointer, data_pointer)` pairs of the most recent steps, oldest
+	/// first, keeping at most [`Config::trace_length`] of them. Useful as a crash log after a
+	/// runtime error.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine, TapeMode};
+	///
+	/// let bytecode = compile(String::from("+>+>+")).unwrap();
+	/// let config = Config {
+	///     tape_mode: TapeMode::Fixed(2),
+	///     trace_length: 2,
+	///     ..Config::default()
+	/// };
+	/// let (mut input, mut output) = (&b""[..], Vec::new());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// assert!(machine.run().is_err());
+	/// assert_eq!(machine.trace(), &[(2, 1), (3, 1)]);
+	/// ```
+	pub fn trace(&self) -> &VecDeque<(usize, isize)> {
+		&self.trace
+	}
+
+	/// Returns the visited cells, from the leftmost to the rightmost one.
+	pub fn memory(&self) -> &[u32] {
+		&self.state.memory[self.state.start..self.state.end]
+	}
+
+	/// Returns the value of the cell at `index`, relative to the starting cell.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from("<<+>>>>>++")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// machine.run().unwrap();
+	/// assert_eq!(machine.data_pointer(), 3);
+	/// assert_eq!((machine.cell(-2), machine.cell(0), machine.cell(3)), (1, 0, 2));
+	/// assert_eq!(machine.cell(100), 0);
+	/// ```
+	pub fn cell(&self, index: isize) -> u32 {
+		self.state.cell(index)
+	}
+
+	/// Returns the position of the data pointer relative to the starting cell, which is
+	/// negative once the pointer has moved left of it.
+	pub fn data_pointer(&self) -> isize {
+		self.state.position()
+	}
+
+	pub fn instruction_pointer(&self) -> usize {
+		self.state.instruction_pointer
+	}
+
+	pub fn is_halted(&self) -> bool {
+		self.state.instruction_pointer >= self.bytecode.len()
+	}
+
+	pub fn current_instruction(&self) -> Option<&'a Instruction> {
+		self.bytecode
+			.instructions
+			.get(self.state.instruction_pointer)
+	}
+
+	/// Executes a single instruction and reports whether the program has halted.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine, StepOutcome};
+	///
+	/// let bytecode = compile(String::from("+>+")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// assert_eq!(machine.step().unwrap(), StepOutcome::Running);
+	/// assert_eq!(machine.step().unwrap(), StepOutcome::Running);
+	/// assert_eq!(machine.data_pointer(), 1);
+	/// assert_eq!(machine.step().unwrap(), StepOutcome::Halted);
+	/// assert_eq!(machine.memory(), &[1, 1]);
+	/// ```
+	pub fn step(&mut self) -> Result<StepOutcome, RuntimeError> {
+		self.step_with(None, None)
+	}
+
+	/// Executes the next instruction, using `input` and `output` instead of the machine's own
+	/// reader and writer if given.
+	fn step_with(
+		&mut self,
+		input: Option<&mut dyn Read>,
+		output: Option<&mut dyn Write>,
+	) -> Result<StepOutcome, RuntimeError> {
+		let Some(instruction) = self.current_instruction() else {
+			return Ok(StepOutcome::Halted);
+		};
+		let offset = self.bytecode.source_offsets[self.state.instruction_pointer];
+		let error_at = |kind| RuntimeError { kind, offset };
+		if !self.state.initial_tape_added {
+			self.state.initial_tape_added = true;
+			self.state
+				.add_cells(
+					&self.bytecode.initial_tape,
+					self.bytecode.initial_data_pointer,
+					self.config.tape_mode,
+					self.config.cell_width,
+				)
+				.map_err(error_at)?;
+		}
+		if self
+			.config
+			.max_steps
+			.is_some_and(|max_steps| self.steps >= max_steps)
+		{
+			return Err(error_at(RuntimeErrorKind::StepLimitExceeded));
+		}
+		if self.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL) {
+			if let Some(timeout) = self.config.timeout {
+				let started = *self.started.get_or_insert_with(Instant::now);
+				if started.elapsed() >= timeout {
+					return Err(error_at(RuntimeErrorKind::TimeLimitExceeded));
+				}
+			}
+			if self
+				.config
+				.interrupt
+				.as_ref()
+				.is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+			{
+				return Err(error_at(RuntimeErrorKind::Interrupted));
+			}
+		}
+		self.steps += 1;
+		if self.config.trace_length > 0 {
+			if self.trace.len() == self.config.trace_length {
+				self.trace.pop_front();
+			}
+			self.trace
+				.push_back((self.state.instruction_pointer, self.state.position()));
+		}
+		let input: &mut dyn Read = match input {
+			Some(input) => input,
+			None => self.input,
+		};
+		let output: &mut dyn Write = match output {
+			Some(output) => output,
+			None => self.output,
+		};
+		let config = self.config;
+		let state = &mut self.state;
+		match *instruction {
+			Instruction::IncPointer => state.add_pointer(1, config.tape_mode),
+			Instruction::DecPointer => state.sub_pointer(1, config.tape_mode),
+			Instruction::IncByte => state.add_byte(1, config.cell_width),
+			Instruction::DecByte => state.sub_byte(1, config.cell_width),
+			Instruction::AddPointer(amount) => state.add_pointer(amount, config.tape_mode),
+			Instruction::SubPointer(amount) => state.sub_pointer(amount, config.tape_mode),
+			Instruction::AddByte(amount) => state.add_byte(amount, config.cell_width),
+			Instruction::SubByte(amount) => state.sub_byte(amount, config.cell_width),
+			Instruction::MulAdd { offset, factor } => {
+				state.mul_add(offset, factor, config.tape_mode, config.cell_width)
+			}
+			Instruction::SetZero => state.set_zero(),
+			Instruction::Debug => {
+				let position = self.bytecode.source_position(state.instruction_pointer);
+				match &mut self.error_output {
+					Some(error_output) => state.debug(*error_output, position),
+					None => state.debug(&mut io::stderr(), position),
+				}
+			}
+			Instruction::Halt => state.halt(self.bytecode.len()),
+			Instruction::Output => state
+				.output(
+					output,
+					config.output_mode,
+					config.cell_width,
+					config.signed_cells,
+				)
+				.and_then(|()| match &mut self.error_output {
+					_ if !config.log_output => Ok(()),
+					Some(error_output) => state.log_output(*error_output),
+					None => state.log_output(&mut io::stderr()),
+				}),
+			Instruction::Input => output
+				.flush()
+				.map_err(|_| RuntimeErrorKind::OutputFailed)
+				.and_then(|()| {
+					state.input(
+						input,
+						config.input_mode,
+						config.eof_mode,
+						config.cell_width,
+						config.strip_input_newlines,
+					)
+				}),
+			Instruction::OpenBracket { jump_location } => state.open_bracket(jump_location),
+			Instruction::CloseBracket { jump_location } => state.close_bracket(jump_location),
+			_ => Ok(()),
+		}
+		.map_err(error_at)?;
+		if state.instruction_pointer < self.bytecode.len() {
+			return Ok(StepOutcome::Running);
+		}
+		state
+			.finish_output(output, config.output_mode)
+			.map_err(error_at)?;
+		Ok(StepOutcome::Halted)
+	}
+
+	/// Runs the program until it halts.
+	pub fn run(&mut self) -> Result<(), RuntimeError> {
+		while self.step()? == StepOutcome::Running {}
+		Ok(())
+	}
+
+	/// Executes at least one instruction and keeps going until the next instruction originates
+	/// from one of the given source offsets or the program halts.
+	///
+	/// ```
+	/// use brainfuck::{compile, BreakpointOutcome, Config, Machine};
+	/// use std::collections::HashSet;
+	///
+	/// let bytecode = compile(String::from("+++[>+<-.]")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// let breakpoints = HashSet::from([5]);
+	/// assert_eq!(machine.run_to_breakpoint(&breakpoints).unwrap(), BreakpointOutcome::Breakpoint(5));
+	/// assert_eq!(machine.memory(), &[3, 0]);
+	/// assert_eq!(machine.run_to_breakpoint(&breakpoints).unwrap(), BreakpointOutcome::Breakpoint(5));
+	/// assert_eq!(machine.memory(), &[2, 1]);
+	/// ```
+	pub fn run_to_breakpoint(
+		&mut self,
+		offsets: &HashSet<usize>,
+	) -> Result<BreakpointOutcome, RuntimeError> {
+		loop {
+			if self.step()? == StepOutcome::Halted {
+				return Ok(BreakpointOutcome::Halted);
+			}
+			let offset = self.bytecode.source_offsets[self.state.instruction_pointer];
+			if offsets.contains(&offset) {
+				return Ok(BreakpointOutcome::Breakpoint(offset));
+			}
+		}
+	}
+
+	/// Runs the program until it is about to execute `,`, has printed a byte, or halts, so the
+	/// host can run it as a coroutine. Calling it again after [`RunOutcome::NeedsInput`] executes
+	/// the `,`, reading from the reader given to [`Machine::new`], which the host can fill in
+	/// between calls.
+	///
+	/// Output is returned one byte at a time instead of being written to the machine's writer.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine, RunOutcome};
+	///
+	/// let bytecode = compile(String::from(",+.")).unwrap();
+	/// let (mut input, mut output, config) = (&b"a"[..], Vec::new(), Config::default());
+	/// let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// assert_eq!(machine.run_until_input().unwrap(), RunOutcome::NeedsInput);
+	/// assert_eq!(machine.run_until_input().unwrap(), RunOutcome::Output(b'b'));
+	/// assert_eq!(machine.run_until_input().unwrap(), RunOutcome::Halted);
+	/// ```
+	pub fn run_until_input(&mut self) -> Result<RunOutcome, RuntimeError> {
+		let mut output = mem::take(&mut self.pending_output);
+		let outcome = loop {
+			if let Some(byte) = output.pop_front() {
+				break Ok(RunOutcome::Output(byte));
+			}
+			if self.current_instruction() == Some(&Instruction::Input) && !self.input_requested {
+				self.input_requested = true;
+				break Ok(RunOutcome::NeedsInput);
+			}
+			self.input_requested = false;
+			match self.step_with(None, Some(&mut output)) {
+				Ok(StepOutcome::Halted) if output.is_empty() => break Ok(RunOutcome::Halted),
+				Ok(_) => {}
+				Err(error) => break Err(error),
+			}
+		};
+		self.pending_output = output;
+		outcome
+	}
+
+	/// Turns the machine into an iterator over its output bytes, running the program only as far
+	/// as needed to produce the next byte. Input is pulled from `input` one byte at a time when the
+	/// program executes `,`, where `None` means the end of input.
+	///
+	/// The reader and writer given to [`Machine::new`] are no longer used. The iterator ends after
+	/// the program halts or yields a runtime error.
+	///
+	/// ```
+	/// use brainfuck::{compile, Config, Machine};
+	///
+	/// let bytecode = compile(String::from(",[.[-],]")).unwrap();
+	/// let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	/// let machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	/// let mut bytes = b"abc".iter().copied();
+	/// let output: Vec<u8> = machine
+	///     .output_iter(move || bytes.next())
+	///     .collect::<Result<_, _>>()
+	///     .unwrap();
+	/// assert_eq!(output, b"abc");
+	/// ```
+	pub fn output_iter<F>(self, input: F) -> OutputIter<'a, F>
+	where
+		F: FnMut() -> Option<u8>,
+	{
+		OutputIter {
+			machine: self,
+			input: CallbackReader(input),
+			buffer: VecDeque::new(),
+			finished: false,
+		}
+	}
+}
+
+struct CallbackReader<F>(F);
+
+impl<F: FnMut() -> Option<u8>> Read for CallbackReader<F> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match buf.first_mut() {
+			Some(first) => Ok((self.0)().map_or(0, |byte| {
+				*first = byte;
+				1
+			})),
+			None => Ok(0),
+		}
+	}
+}
+
+/// Iterator over the output of a [`Machine`], created by [`Machine::output_iter`].
+pub struct OutputIter<'a, F> {
+	machine: Machine<'a>,
+	input: CallbackReader<F>,
+	buffer: VecDeque<u8>,
+	finished: bool,
+}
+
+impl<F: FnMut() -> Option<u8>> Iterator for OutputIter<'_, F> {
+	type Item = Result<u8, RuntimeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(byte) = self.buffer.pop_front() {
+				return Some(Ok(byte));
+			}
+			if self.finished {
+				return None;
+			}
+			match self
+				.machine
+				.step_with(Some(&mut self.input), Some(&mut self.buffer))
+			{
+				Ok(StepOutcome::Running) => {}
+				Ok(StepOutcome::Halted) => self.finished = true,
+				Err(error) => {
+					self.finished = true;
+					return Some(Err(error));
+				}
+			}
+		}
+	}
+}
+
+/// Executes compiled bytecode, reading input from `input` and writing output to `output`.
+///
+/// Output is buffered according to the configured [`BufferMode`](crate::BufferMode) and flushed
+/// before reading input and once the program finishes, including when it stops with a runtime error.
+///
+/// ```
+/// use brainfuck::{compile, execute, Config, TapeMode};
+///
+/// let bytecode = compile(String::from(",+.")).unwrap();
+/// let mut output = Vec::new();
+/// execute(&bytecode, &mut &b"A"[..], &mut output, &Config::default()).unwrap();
+/// assert_eq!(output, b"B");
+///
+/// let bytecode = compile(String::from("+++.>.")).unwrap();
+/// let config = Config {
+///     tape_mode: TapeMode::Fixed(1),
+///     ..Config::default()
+/// };
+/// let mut output = Vec::new();
+/// assert!(execute(&bytecode, &mut &b""[..], &mut output, &config).is_err());
+/// assert_eq!(output, [3]);
+/// ```
+pub fn execute(
+	bytecode: &Bytecode,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+	config: &Config,
+) -> Result<(), RuntimeError> {
+	let mut output = buffer_output(output, config.buffer_mode);
+	let result = Machine::new(bytecode, input, &mut output, config).run();
+	let flushed = flush_output(bytecode, &mut output);
+	result.and(flushed)
+}
+
+/// Like [`execute`], but writes the memory dumps of `#` to `error_output` instead of stderr, so
+/// `output` only receives what the program prints with `.`.
+///
+/// ```
+/// use brainfuck::{compile_with_config, execute_with_error_output, Config};
+///
+/// let config = Config {
+///     debug: true,
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config(String::from("+#."), &config).unwrap();
+/// let (mut output, mut error_output) = (Vec::new(), Vec::new());
+/// execute_with_error_output(&bytecode, &mut &b""[..], &mut output, &mut error_output, &config)
+///     .unwrap();
+/// assert_eq!(output, [1]);
+/// assert!(String::from_utf8(error_output).unwrap().starts_with("Debug at line 1, column 2\n"));
+/// ```
+pub fn execute_with_error_output(
+	bytecode: &Bytecode,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+	error_output: &mut dyn Write,
+	config: &Config,
+) -> Result<(), RuntimeError> {
+	let mut output = buffer_output(output, config.buffer_mode);
+	let result = Machine::new(bytecode, input, &mut output, config)
+		.with_error_output(error_output)
+		.run();
+	let flushed = flush_output(bytecode, &mut output);
+	result.and(flushed)
+}
+
+/// The tape and data pointer a program left behind, returned by [`execute_capturing`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FinalState {
+	/// The visited cells, from the leftmost to the rightmost one.
+	pub memory: Vec<u32>,
+	/// The index of the starting cell in `memory`.
+	pub origin: usize,
+	/// The position of the data pointer relative to the starting cell.
+	pub data_pointer: isize,
+	/// The value of every cell outside `memory`, see [`Config::initial_cell_value`].
+	pub initial_cell: u32,
+}
+
+impl FinalState {
+	/// Returns the value of the cell at `index`, relative to the starting cell.
+	pub fn cell(&self, index: isize) -> u32 {
+		self.origin
+			.checked_add_signed(index)
+			.and_then(|index| self.memory.get(index))
+			.copied()
+			.unwrap_or(self.initial_cell)
+	}
+
+	/// Returns the value of the cell under the data pointer.
+	pub fn current_cell(&self) -> u32 {
+		self.cell(self.data_pointer)
+	}
+}
+
+/// Like [`execute`], but returns the final tape and data pointer so they can be inspected after
+/// a successful run.
+///
+/// ```
+/// use brainfuck::{compile, execute_capturing, Config};
+///
+/// let bytecode = compile(String::from("+++")).unwrap();
+/// let config = Config::default();
+/// let state = execute_capturing(&bytecode, &mut &b""[..], &mut Vec::new(), &config).unwrap();
+/// assert_eq!(state.current_cell(), 3);
+/// assert_eq!(state.data_pointer, 0);
+/// ```
+pub fn execute_capturing(
+	bytecode: &Bytecode,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+	config: &Config,
+) -> Result<FinalState, RuntimeError> {
+	let mut output = buffer_output(output, config.buffer_mode);
+	let mut machine = Machine::new(bytecode, input, &mut output, config);
+	let result = machine.run();
+	let state = FinalState {
+		memory: machine.memory().to_vec(),
+		origin: machine.state.origin - machine.state.start,
+		data_pointer: machine.data_pointer(),
+		initial_cell: machine.state.initial_cell,
+	};
+	let flushed = flush_output(bytecode, &mut output);
+	result.and(flushed).map(|()| state)
+}
+
+pub(crate) fn buffer_output<'a>(
+	output: &'a mut dyn Write,
+	buffer_mode: BufferMode,
+) -> Box<dyn Write + 'a> {
+	match buffer_mode {
+		BufferMode::Full => Box::new(BufWriter::new(output)),
+		BufferMode::Line => Box::new(LineWriter::new(output)),
+	}
+}
+
+pub(crate) fn flush_output(
+	bytecode: &Bytecode,
+	output: &mut dyn Write,
+) -> Result<(), RuntimeError> {
+	output.flush().map_err(|_| RuntimeError {
+		kind: RuntimeErrorKind::OutputFailed,
+		offset: bytecode.source_offsets.last().copied().unwrap_or(0),
+	})
+}
@@ -0,0 +1,33 @@
+mod analyzer;
+mod compiler;
+mod config;
+mod disassembler;
+mod interpreter;
+mod linter;
+mod optimizer;
+mod profiler;
+pub mod programs;
+mod runner;
+mod serializer;
+mod transpiler;
+
+pub use analyzer::analyze_pointer_range;
+pub use compiler::{
+	code_density, compile, compile_str, compile_with_config, compile_with_stages, jump_table,
+	Bytecode, CompileError, CompileErrorKind, Instruction,
+};
+pub use config::{
+	BufferMode, CellWidth, CharMap, Config, EofMode, InputMode, LeftBoundary, OptLevel, OutputMode,
+	TapeMode,
+};
+pub use disassembler::{assemble, disassemble, disassemble_hot, AssembleError};
+pub use interpreter::{
+	execute, execute_capturing, execute_with_error_output, BreakpointOutcome, FinalState, Machine,
+	OutputIter, RunOutcome, RuntimeError, RuntimeErrorKind, Snapshot, StepOutcome,
+	TIMEOUT_CHECK_INTERVAL,
+};
+pub use linter::{diagnose, diagnose_with_config, lint, Diagnostic, Warning, WarningKind};
+pub use profiler::{profile, profile_partial, Profile};
+pub use runner::{execute_bounded, run_capture, run_str, split_program_input, BfError};
+pub use serializer::{deserialize, serialize, DeserializeError};
+pub use transpiler::{transpile_c, transpile_rust, transpile_wat};
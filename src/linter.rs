@@ -0,0 +1,158 @@
+use crate::{
+	compiler::{character_span, characters, is_allowed, Bytecode, Instruction},
+	config::Config,
+	optimizer,
+};
+use std::{fmt, ops::Range};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+	InfiniteLoop,
+	Unreachable,
+}
+
+impl fmt::Display for WarningKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WarningKind::InfiniteLoop => write!(f, "Loop never terminates once entered"),
+			WarningKind::Unreachable => write!(f, "Code after an unconditional halt never runs"),
+		}
+	}
+}
+
+/// Code that compiles but is most likely a mistake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Warning {
+	pub kind: WarningKind,
+	pub offset: usize,
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Warning: {} at offset {}", self.kind, self.offset)
+	}
+}
+
+/// A loop body that returns to the loop cell without any I/O and without changing that cell
+/// tests the same value every time, so the loop spins forever once entered.
+fn leaves_cell_unchanged(body: &[Instruction]) -> bool {
+	optimizer::pointer_neutral_deltas(body).is_some_and(|deltas| !deltas.contains_key(&0))
+}
+
+/// Looks for loops that can never terminate once entered, such as the empty loop in `+[]`, and
+/// for code following a halt outside of any loop, such as the `++` in `+@++`.
+///
+/// The loop check is conservative, a loop that ends on another cell than it started on, does
+/// I/O or contains another loop is never flagged. It assumes every move reaches a different
+/// cell, which a wrapping or clamped tape may not honour.
+///
+/// ```
+/// use brainfuck::{compile, lint, WarningKind};
+///
+/// let warnings = lint(&compile(String::from("+[]")).unwrap());
+/// assert_eq!(warnings.len(), 1);
+/// assert_eq!(warnings[0].kind, WarningKind::InfiniteLoop);
+/// assert_eq!(warnings[0].offset, 1);
+///
+/// assert!(lint(&compile(String::from("+[-]")).unwrap()).is_empty());
+/// ```
+pub fn lint(bytecode: &Bytecode) -> Vec<Warning> {
+	let mut warnings = infinite_loops(bytecode);
+	warnings.extend(unreachable_code(bytecode));
+	warnings
+}
+
+fn infinite_loops(bytecode: &Bytecode) -> Vec<Warning> {
+	let instructions = bytecode.instructions();
+	instructions
+		.iter()
+		.enumerate()
+		.filter_map(|(i, instruction)| match *instruction {
+			Instruction::OpenBracket { jump_location }
+				if leaves_cell_unchanged(&instructions[i + 1..jump_location]) =>
+			{
+				Some(Warning {
+					kind: WarningKind::InfiniteLoop,
+					offset: bytecode.source_offsets[i],
+				})
+			}
+			_ => None,
+		})
+		.collect()
+}
+
+/// Reports the first instruction after a halt at loop depth zero, since a halt there is always
+/// reached.
+fn unreachable_code(bytecode: &Bytecode) -> Option<Warning> {
+	let mut depth = 0;
+	let halt = bytecode.instructions().iter().position(|instruction| {
+		match instruction {
+			Instruction::OpenBracket { .. } => depth += 1,
+			Instruction::CloseBracket { .. } => depth -= 1,
+			_ => {}
+		}
+		depth == 0 && *instruction == Instruction::Halt
+	})?;
+	let offset = bytecode.source_offset(halt + 1)?;
+	Some(Warning {
+		kind: WarningKind::Unreachable,
+		offset,
+	})
+}
+
+/// A problem in the source code, covering the byte range `range`, for showing in an editor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub range: Range<usize>,
+	pub message: String,
+}
+
+/// Reports every unmatched bracket in `source_code` without compiling or running it.
+///
+/// ```
+/// use brainfuck::diagnose;
+///
+/// let diagnostics = diagnose("+[>]]");
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].range, 4..5);
+/// assert_eq!(diagnostics[0].message, "Unmatched ']'");
+/// ```
+pub fn diagnose(source_code: &str) -> Vec<Diagnostic> {
+	diagnose_with_config(source_code, &Config::default())
+}
+
+/// Like [`diagnose`], but recognizes commands through `config`, and also reports characters
+/// that are neither commands nor whitespace if `config.strict` is set.
+pub fn diagnose_with_config(source_code: &str, config: &Config) -> Vec<Diagnostic> {
+	let mut diagnostics = Vec::new();
+	let mut open_brackets = Vec::new();
+	for (offset, character) in characters(source_code.as_bytes()) {
+		let range = character_span(offset, character);
+		match character.and_then(|character| config.char_map.command(character)) {
+			Some('[') => open_brackets.push(range),
+			Some(']') if open_brackets.pop().is_none() => {
+				diagnostics.push(Diagnostic {
+					range,
+					message: String::from("Unmatched ']'"),
+				});
+			}
+			Some(']') => (),
+			_ if config.strict && !is_allowed(character, config) => {
+				diagnostics.push(Diagnostic {
+					range,
+					message: format!(
+						"Unexpected character '{}'",
+						character.unwrap_or(char::REPLACEMENT_CHARACTER)
+					),
+				});
+			}
+			_ => (),
+		}
+	}
+	diagnostics.extend(open_brackets.into_iter().map(|range| Diagnostic {
+		range,
+		message: String::from("Unmatched '['"),
+	}));
+	diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+	diagnostics
+}
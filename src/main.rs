@@ -1,230 +1,463 @@
-use clap::Parser;
-use std::{collections::VecDeque, fs, process, str};
-use text_io::read;
-use thiserror::Error;
-
-#[derive(Clone)]
-enum Instruction {
-	IncPointer,
-	DecPointer,
-	IncByte,
-	DecByte,
-	Output,
-	Input,
-	EmptyOpenBracket,
-	EmptyCloseBracket,
-	OpenBracket { jump_location: usize },
-	CloseBracket { jump_location: usize },
-}
-
-type Bytecode = Vec<Instruction>;
-
-#[derive(Error, Debug)]
-enum CompileError {
-	#[error("Compile error: Unbalanced brackets")]
-	UnbalancedBrackets,
-}
-
-#[derive(Error, Debug)]
-enum RuntimeError {
-	#[error("Runtime error: Out of memory")]
-	OutOfMemory,
-	#[error("Runtime error: Input failed")]
-	InputFailed,
-}
-
-fn parse_character(character: char) -> Option<Instruction> {
-	match character {
-		'>' => Some(Instruction::IncPointer),
-		'<' => Some(Instruction::DecPointer),
-		'+' => Some(Instruction::IncByte),
-		'-' => Some(Instruction::DecByte),
-		'.' => Some(Instruction::Output),
-		',' => Some(Instruction::Input),
-		'[' => Some(Instruction::EmptyOpenBracket),
-		']' => Some(Instruction::EmptyCloseBracket),
-		_ => None,
-	}
+use brainfuck::{
+	assemble, compile_with_config, compile_with_stages, deserialize, disassemble, disassemble_hot,
+	execute, lint, profile_partial, serialize, split_program_input, transpile_c, transpile_rust,
+	transpile_wat, BufferMode, Bytecode, Config, InputMode, Machine, OptLevel, RuntimeError,
+	RuntimeErrorKind, StepOutcome,
+};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::{
+	error::Error,
+	fs::{self, File},
+	io::{self, BufRead, BufReader, Cursor, IsTerminal, Read, Write},
+	process,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
+const PREVIEW_RADIUS: usize = 8;
+
+#[derive(Clone, ValueEnum)]
+enum Target {
+	C,
+	Rust,
+	Wat,
 }
 
-fn brackets_are_balanced(bytecode: &Bytecode) -> bool {
-	let open_count = bytecode
-		.iter()
-		.filter(|instruction| matches!(instruction, Instruction::EmptyOpenBracket))
-		.count();
-	let close_count = bytecode
-		.iter()
-		.filter(|instruction| matches!(instruction, Instruction::EmptyCloseBracket))
-		.count();
-	open_count == close_count
+#[derive(clap::Args)]
+struct CompileArgs {
+	path: Option<String>,
+	#[arg(long, conflicts_with = "path")]
+	eval: Option<String>,
+	#[arg(long)]
+	max_loop_depth: Option<usize>,
+	#[arg(long)]
+	no_optimize: bool,
+	#[arg(long, conflicts_with = "no_optimize")]
+	fold_constants: bool,
+	#[arg(long)]
+	debug: bool,
+	#[arg(long)]
+	halt: bool,
+	#[arg(long)]
+	strict: bool,
+	#[arg(long)]
+	lint: bool,
+	#[arg(long)]
+	verbose: bool,
 }
 
-fn match_brackets(mut bytecode: Bytecode) -> Result<Bytecode, CompileError> {
-	if !brackets_are_balanced(&bytecode) {
-		return Err(CompileError::UnbalancedBrackets);
-	}
+#[derive(clap::Args)]
+struct RunArgs {
+	#[command(flatten)]
+	source: CompileArgs,
+	#[arg(long, conflicts_with_all = ["path", "eval"])]
+	run_bytecode: Option<String>,
+	#[arg(long, conflicts_with_all = ["path", "eval", "run_bytecode"])]
+	run_listing: Option<String>,
+	#[arg(long)]
+	input: Option<String>,
+	#[arg(long, conflicts_with_all = ["input", "run_bytecode", "run_listing"])]
+	split_input: bool,
+	#[arg(long, conflicts_with = "input")]
+	no_input: bool,
+	#[arg(long)]
+	strip_input_newlines: bool,
+	#[arg(long)]
+	echo_input: bool,
+	#[arg(long)]
+	max_steps: Option<u64>,
+	#[arg(long)]
+	max_tape_cells: Option<usize>,
+	#[arg(long)]
+	max_output: Option<usize>,
+	#[arg(long, value_parser = parse_timeout)]
+	timeout: Option<Duration>,
+	#[arg(long)]
+	profile: bool,
+	#[arg(long, conflicts_with = "profile")]
+	stats: bool,
+	#[arg(long, conflicts_with_all = ["profile", "stats"])]
+	hot: bool,
+	#[arg(long)]
+	dump_memory: bool,
+	#[arg(long)]
+	dump_chars: bool,
+	#[arg(long)]
+	log_output: bool,
+	#[arg(long, conflicts_with_all = ["profile", "stats", "hot", "dump_memory"])]
+	memory_preview: bool,
+	#[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+	preview_rate: u32,
+	#[arg(long, conflicts_with_all = ["profile", "stats", "hot", "dump_memory", "memory_preview"])]
+	trace: bool,
+}
 
-	let mut open_locations_stack = Vec::new();
-	for (i, instruction) in bytecode.clone().iter().enumerate() {
-		match instruction {
-			Instruction::EmptyOpenBracket => {
-				open_locations_stack.push(i);
-			}
-			Instruction::EmptyCloseBracket => {
-				let open_location = open_locations_stack
-					.pop()
-					.ok_or(CompileError::UnbalancedBrackets)?;
-				bytecode[i] = Instruction::CloseBracket {
-					jump_location: open_location,
-				};
-				bytecode[open_location] = Instruction::OpenBracket { jump_location: i };
-			}
-			_ => (),
-		}
-	}
-	Ok(bytecode)
+#[derive(Subcommand)]
+enum Command {
+	/// Run a program, the default when no subcommand is given
+	Run(RunArgs),
+	/// Transpile a program to another language
+	Emit {
+		#[arg(value_enum)]
+		target: Target,
+		#[command(flatten)]
+		source: CompileArgs,
+	},
+	/// Print the compiled bytecode in a human-readable form
+	Disasm(CompileArgs),
+	/// Run lines typed in one by one on a tape that persists between them
+	Repl,
+	/// Compile a program to bytecode that can be run later with --run-bytecode
+	Compile {
+		#[command(flatten)]
+		source: CompileArgs,
+		#[arg(short, long)]
+		output: String,
+	},
 }
 
-fn compile(source_code: String) -> Result<Bytecode, CompileError> {
-	let bytecode: Vec<Instruction> = source_code.chars().filter_map(parse_character).collect();
-	match_brackets(bytecode)
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+	#[command(flatten)]
+	run: RunArgs,
 }
 
-struct State {
-	memory: VecDeque<u8>,
-	data_pointer: usize,
-	instruction_pointer: usize,
+fn parse_timeout(secs: &str) -> Result<Duration, String> {
+	let secs = secs.parse::<f64>().map_err(|error| error.to_string())?;
+	Duration::try_from_secs_f64(secs).map_err(|error| error.to_string())
 }
 
-impl State {
-	fn new() -> Self {
-		Self {
-			memory: VecDeque::from(vec![0u8]),
-			data_pointer: 0,
-			instruction_pointer: 0,
-		}
+type Result<T, E = Box<dyn Error>> = std::result::Result<T, E>;
+
+fn read_stdin() -> io::Result<Vec<u8>> {
+	let mut source_code = Vec::new();
+	io::stdin().read_to_end(&mut source_code)?;
+	Ok(source_code)
+}
+
+fn read_source_code(eval: Option<String>, path: Option<&str>) -> io::Result<Vec<u8>> {
+	match (eval, path) {
+		(Some(source_code), _) => Ok(source_code.into_bytes()),
+		(None, Some("-")) => read_stdin(),
+		(None, None) if !io::stdin().is_terminal() => read_stdin(),
+		(None, Some(path)) => fs::read(path),
+		(None, None) => Cli::command()
+			.error(
+				ErrorKind::MissingRequiredArgument,
+				"either <PATH> or --eval <EVAL> is required",
+			)
+			.exit(),
 	}
+}
 
-	fn inc_pointer(mut self) -> Result<Self, RuntimeError> {
-		if self.data_pointer == usize::MAX {
-			return Err(RuntimeError::OutOfMemory);
-		}
-		self.data_pointer += 1;
-		if self.data_pointer == self.memory.len() {
-			self.memory.push_back(0u8);
-		}
-		self.instruction_pointer += 1;
-		Ok(self)
+/// Copies every byte read through it to stderr, like a terminal echoing what was typed. The
+/// interpreter reads one byte per `,`, so only consumed input is echoed.
+struct EchoInput<R>(R);
+
+impl<R: Read> Read for EchoInput<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.0.read(buf)?;
+		io::stderr().write_all(&buf[..read])?;
+		Ok(read)
 	}
+}
 
-	fn dec_pointer(mut self) -> Result<Self, RuntimeError> {
-		if self.data_pointer == 0 && self.memory.len() == usize::MAX {
-			return Err(RuntimeError::OutOfMemory);
-		}
-		if self.data_pointer == 0 {
-			self.memory.push_front(0u8);
-		} else {
-			self.data_pointer -= 1;
+fn open_input(path: Option<&str>) -> io::Result<Box<dyn Read>> {
+	Ok(match path {
+		Some(path) => Box::new(BufReader::new(File::open(path)?)),
+		None => Box::new(io::stdin().lock()),
+	})
+}
+
+fn load_bytecode(path: &str) -> Result<Bytecode> {
+	Ok(deserialize(&fs::read(path)?)?)
+}
+
+fn load_listing(path: &str) -> Result<Bytecode> {
+	Ok(assemble(&fs::read_to_string(path)?)?)
+}
+
+/// Makes Ctrl-C stop the program cleanly through [`Config::interrupt`]. A second Ctrl-C exits
+/// immediately, e.g. when the program is blocked waiting for input.
+fn install_interrupt_handler() -> Option<Arc<AtomicBool>> {
+	let interrupt = Arc::new(AtomicBool::new(false));
+	let handler_interrupt = Arc::clone(&interrupt);
+	ctrlc::set_handler(move || {
+		if handler_interrupt.swap(true, Ordering::Relaxed) {
+			process::exit(130);
 		}
-		self.instruction_pointer += 1;
-		Ok(self)
-	}
+	})
+	.ok()?;
+	Some(interrupt)
+}
 
-	fn inc_byte(mut self) -> Result<Self, RuntimeError> {
-		self.memory[self.data_pointer] = self.memory[self.data_pointer].wrapping_add(1);
-		self.instruction_pointer += 1;
-		Ok(self)
-	}
+fn interrupted(config: &Config) -> bool {
+	config
+		.interrupt
+		.as_ref()
+		.is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+}
 
-	fn dec_byte(mut self) -> Result<Self, RuntimeError> {
-		self.memory[self.data_pointer] = self.memory[self.data_pointer].wrapping_sub(1);
-		self.instruction_pointer += 1;
-		Ok(self)
+/// Exits with the status a shell reports for Ctrl-C when the program was interrupted.
+fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+	match error.downcast_ref::<RuntimeError>() {
+		Some(RuntimeError {
+			kind: RuntimeErrorKind::Interrupted,
+			..
+		}) => 130,
+		_ => 1,
 	}
+}
 
-	fn output(mut self) -> Result<Self, RuntimeError> {
-		print!("{}", self.memory[self.data_pointer] as char);
-		self.instruction_pointer += 1;
-		Ok(self)
+fn compile_config(args: &CompileArgs) -> Config {
+	Config {
+		max_loop_depth: args.max_loop_depth,
+		opt_level: if args.no_optimize {
+			OptLevel::None
+		} else {
+			OptLevel::Full
+		},
+		fold_constants: args.fold_constants,
+		debug: args.debug,
+		halt: args.halt,
+		strict: args.strict,
+		..Config::default()
 	}
+}
+
+fn compile_source(args: &CompileArgs, config: &Config) -> Result<Bytecode> {
+	let source_code = read_source_code(args.eval.clone(), args.path.as_deref())?;
+	compile_source_code(args, &source_code, config)
+}
 
-	fn input(mut self) -> Result<Self, RuntimeError> {
-		let input: String = read!("{}\n");
-		self.memory[self.data_pointer] = input.bytes().next().ok_or(RuntimeError::InputFailed)?;
-		self.instruction_pointer += 1;
-		Ok(self)
+fn compile_source_code(
+	args: &CompileArgs,
+	source_code: &[u8],
+	config: &Config,
+) -> Result<Bytecode> {
+	let characters = String::from_utf8_lossy(source_code).chars().count();
+	let (bytecode, stages) = compile_with_stages(source_code, config)?;
+	if args.verbose {
+		eprintln!("source: {} characters", characters);
+		for (stage, instructions) in stages {
+			eprintln!("{}: {} instructions", stage, instructions);
+		}
 	}
+	if args.lint {
+		for warning in lint(&bytecode) {
+			eprintln!("{}", warning);
+		}
+	}
+	Ok(bytecode)
+}
 
-	fn open_bracket(mut self, jump_location: usize) -> Result<Self, RuntimeError> {
-		if self.memory[self.data_pointer] == 0 {
-			self.instruction_pointer = jump_location;
+fn run(args: RunArgs) -> Result<()> {
+	let config = Config {
+		max_steps: args.max_steps,
+		max_tape_cells: args.max_tape_cells,
+		max_output: args.max_output,
+		timeout: args.timeout,
+		interrupt: install_interrupt_handler(),
+		dump_chars: args.dump_chars,
+		log_output: args.log_output,
+		input_mode: if args.no_input {
+			InputMode::Disabled
+		} else {
+			InputMode::Bytes
+		},
+		strip_input_newlines: args.strip_input_newlines,
+		buffer_mode: if io::stdout().is_terminal() {
+			BufferMode::Line
 		} else {
-			self.instruction_pointer += 1;
+			BufferMode::Full
+		},
+		..compile_config(&args.source)
+	};
+	let mut embedded_input = None;
+	let bytecode = match (args.run_bytecode, args.run_listing) {
+		(Some(path), _) => load_bytecode(&path)?,
+		(_, Some(path)) => load_listing(&path)?,
+		(None, None) if args.split_input => {
+			let source = &args.source;
+			let source_code = read_source_code(source.eval.clone(), source.path.as_deref())?;
+			let (program, input) = split_program_input(&source_code);
+			embedded_input = Some(input.to_vec());
+			compile_source_code(source, program, &config)?
 		}
-		Ok(self)
+		(None, None) => compile_source(&args.source, &config)?,
+	};
+	let mut input = match embedded_input {
+		Some(input) => Box::new(Cursor::new(input)),
+		None => open_input(args.input.as_deref())?,
+	};
+	if args.echo_input {
+		input = Box::new(EchoInput(input));
 	}
-
-	fn close_bracket(mut self, jump_location: usize) -> Result<Self, RuntimeError> {
-		if self.memory[self.data_pointer] != 0 {
-			self.instruction_pointer = jump_location;
+	let mut output = io::stdout().lock();
+	if args.profile || args.stats || args.hot {
+		let start = Instant::now();
+		let (profile, result) = profile_partial(&bytecode, &mut input, &mut output, &config);
+		if args.stats {
+			eprintln!("Total steps: {}", profile.total_steps);
+			eprintln!("Loop iterations: {}", profile.loop_iterations);
+			eprintln!("Peak tape cells: {}", profile.peak_tape_cells);
+			eprintln!("Wall time: {:.3} s", start.elapsed().as_secs_f64());
+		} else if args.hot {
+			eprint!("{}", disassemble_hot(&bytecode, &profile));
 		} else {
-			self.instruction_pointer += 1;
+			eprint!("{}", profile);
 		}
-		Ok(self)
+		return Ok(result?);
 	}
-}
-
-fn execute(bytecode: &Bytecode) -> Result<(), RuntimeError> {
-	let mut state = State::new();
-	while state.instruction_pointer < bytecode.len() {
-		match bytecode[state.instruction_pointer] {
-			Instruction::IncPointer => {
-				state = state.inc_pointer()?;
-			}
-			Instruction::DecPointer => {
-				state = state.dec_pointer()?;
+	if args.trace {
+		let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+		let mut trace = io::BufWriter::new(io::stderr().lock());
+		let mut result = Ok(());
+		while let Some(instruction) = machine.current_instruction() {
+			let index = machine.instruction_pointer();
+			let outcome = match machine.step() {
+				Ok(outcome) => outcome,
+				Err(error) => {
+					result = Err(error);
+					break;
+				}
+			};
+			writeln!(
+				trace,
+				"{:04} {} dp {} cell {}",
+				index,
+				instruction.mnemonic(),
+				machine.data_pointer(),
+				machine.cell(machine.data_pointer())
+			)
+			.ok();
+			if outcome == StepOutcome::Halted {
+				break;
 			}
-			Instruction::IncByte => {
-				state = state.inc_byte()?;
-			}
-			Instruction::DecByte => {
-				state = state.dec_byte()?;
-			}
-			Instruction::Output => {
-				state = state.output()?;
-			}
-			Instruction::Input => {
-				state = state.input()?;
+		}
+		trace.flush().ok();
+		output.flush().ok();
+		return Ok(result?);
+	}
+	if args.memory_preview {
+		let delay = Duration::from_secs(1) / args.preview_rate;
+		let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+		let result = loop {
+			// The machine only polls the flag every `TIMEOUT_CHECK_INTERVAL` steps, which would
+			// take hours at the preview rate.
+			if interrupted(&config) {
+				break Err(RuntimeError {
+					kind: RuntimeErrorKind::Interrupted,
+					offset: bytecode
+						.source_offset(machine.instruction_pointer())
+						.unwrap_or(0),
+				});
 			}
-			Instruction::OpenBracket { jump_location } => {
-				state = state.open_bracket(jump_location)?;
+			let outcome = machine.step();
+			eprint!("\x1b[2J\x1b[H{}", machine.render_tape(PREVIEW_RADIUS));
+			match outcome {
+				Ok(StepOutcome::Running) => thread::sleep(delay),
+				Ok(StepOutcome::Halted) => break Ok(()),
+				Err(error) => break Err(error),
 			}
-			Instruction::CloseBracket { jump_location } => {
-				state = state.close_bracket(jump_location)?;
+		};
+		output.flush().ok();
+		return Ok(result?);
+	}
+	if args.dump_memory {
+		let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+		let result = machine.run();
+		let dump = machine.dump_memory();
+		output.flush().ok();
+		result?;
+		eprint!("{}", dump);
+		return Ok(());
+	}
+	Ok(execute(&bytecode, &mut input, &mut output, &config)?)
+}
+
+/// Runs every line read from stdin on the tape left behind by the previous ones. `:dump` prints
+/// the tape and `:reset` clears it. A `,` reads from the lines that follow.
+fn repl() -> Result<()> {
+	let config = Config::default();
+	let empty = Bytecode::default();
+	let empty_tape = || Machine::new(&empty, &mut io::empty(), &mut io::sink(), &config).snapshot();
+	let interactive = io::stdin().is_terminal();
+	let mut input = io::stdin().lock();
+	let mut output = io::stdout().lock();
+	let mut tape = empty_tape();
+	loop {
+		if interactive {
+			eprint!("> ");
+		}
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			return Ok(());
+		}
+		match line.trim() {
+			":reset" => tape = empty_tape(),
+			":dump" => {
+				let (mut no_input, mut no_output) = (io::empty(), io::sink());
+				let machine = Machine::new(&empty, &mut no_input, &mut no_output, &config)
+					.with_snapshot(tape.clone());
+				eprint!(
+					"Data pointer: {}\n{}",
+					machine.data_pointer(),
+					machine.dump_memory()
+				);
 			}
-			_ => (),
+			source_code => match compile_with_config(source_code, &config) {
+				Ok(bytecode) => {
+					let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config)
+						.with_snapshot(tape);
+					if let Err(error) = machine.run() {
+						eprintln!("{}", error);
+					}
+					tape = machine.snapshot();
+				}
+				Err(error) => eprintln!("{}", error),
+			},
 		}
+		output.flush()?;
 	}
-	Ok(())
 }
 
-#[derive(Parser)]
-struct Args {
-	path: String,
+fn try_main() -> Result<()> {
+	let cli = Cli::parse();
+	match cli.command {
+		None => run(cli.run)?,
+		Some(Command::Run(args)) => run(args)?,
+		Some(Command::Emit { target, source }) => {
+			let bytecode = compile_source(&source, &compile_config(&source))?;
+			match target {
+				Target::C => print!("{}", transpile_c(&bytecode)),
+				Target::Rust => print!("{}", transpile_rust(&bytecode)),
+				Target::Wat => print!("{}", transpile_wat(&bytecode)),
+			}
+		}
+		Some(Command::Disasm(source)) => {
+			let bytecode = compile_source(&source, &compile_config(&source))?;
+			print!("{}", disassemble(&bytecode));
+		}
+		Some(Command::Compile { source, output }) => {
+			let bytecode = compile_source(&source, &compile_config(&source))?;
+			fs::write(output, serialize(&bytecode))?;
+		}
+		Some(Command::Repl) => repl()?,
+	}
+	Ok(())
 }
 
 fn main() {
-	let args = Args::parse();
-	let source_code = fs::read_to_string(args.path).unwrap_or_else(|error| {
-		println!("{}", error);
-		process::exit(1)
-	});
-	let bytecode = compile(source_code).unwrap_or_else(|error| {
-		println!("{}", error);
-		process::exit(1);
-	});
-	execute(&bytecode).unwrap_or_else(|error| {
-		println!("{}", error);
-		process::exit(1);
-	});
+	if let Err(error) = try_main() {
+		eprintln!("{}", error);
+		process::exit(exit_code(&*error));
+	}
 }
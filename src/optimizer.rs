@@ -0,0 +1,196 @@
+use crate::compiler::{Bytecode, Instruction};
+use std::{collections::BTreeMap, mem};
+
+fn is_foldable(instruction: &Instruction) -> bool {
+	matches!(
+		instruction,
+		Instruction::IncPointer
+			| Instruction::DecPointer
+			| Instruction::IncByte
+			| Instruction::DecByte
+	)
+}
+
+fn fold(instruction: Instruction, count: usize) -> Instruction {
+	match instruction {
+		Instruction::IncPointer => Instruction::AddPointer(count),
+		Instruction::DecPointer => Instruction::SubPointer(count),
+		Instruction::IncByte => Instruction::AddByte(count as u32),
+		Instruction::DecByte => Instruction::SubByte(count as u32),
+		instruction => instruction,
+	}
+}
+
+pub fn fold_runs(bytecode: Bytecode) -> Bytecode {
+	let mut folded = Vec::with_capacity(bytecode.len());
+	let mut instructions = bytecode.into_iter().peekable();
+	while let Some((instruction, offset)) = instructions.next() {
+		if !is_foldable(&instruction) {
+			folded.push((instruction, offset));
+			continue;
+		}
+		let mut count = 1;
+		while instructions
+			.next_if(|(next, _)| mem::discriminant(next) == mem::discriminant(&instruction))
+			.is_some()
+		{
+			count += 1;
+		}
+		folded.push((fold(instruction, count), offset));
+	}
+	folded.into_iter().collect()
+}
+
+fn is_clear_loop(window: &[Instruction]) -> bool {
+	matches!(
+		window,
+		[
+			Instruction::OpenBracket { .. },
+			Instruction::IncByte
+				| Instruction::DecByte
+				| Instruction::AddByte(1)
+				| Instruction::SubByte(1),
+			Instruction::CloseBracket { .. },
+		]
+	)
+}
+
+pub fn clear_loops(bytecode: Bytecode) -> Bytecode {
+	let mut cleared = Vec::with_capacity(bytecode.len());
+	let mut i = 0;
+	while i < bytecode.len() {
+		let offset = bytecode.source_offsets[i];
+		if bytecode
+			.instructions
+			.get(i..i + 3)
+			.is_some_and(is_clear_loop)
+		{
+			cleared.push((Instruction::SetZero, offset));
+			i += 3;
+		} else {
+			cleared.push((bytecode.instructions[i].clone(), offset));
+			i += 1;
+		}
+	}
+	cleared.into_iter().collect()
+}
+
+fn loop_step(instruction: &Instruction) -> Option<(isize, u32)> {
+	match *instruction {
+		Instruction::IncPointer => Some((1, 0)),
+		Instruction::DecPointer => Some((-1, 0)),
+		Instruction::AddPointer(amount) => Some((isize::try_from(amount).ok()?, 0)),
+		Instruction::SubPointer(amount) => Some((-isize::try_from(amount).ok()?, 0)),
+		Instruction::IncByte => Some((0, 1)),
+		Instruction::DecByte => Some((0, u32::MAX)),
+		Instruction::AddByte(amount) => Some((0, amount)),
+		Instruction::SubByte(amount) => Some((0, amount.wrapping_neg())),
+		_ => None,
+	}
+}
+
+/// Adds up what a loop body made only of pointer moves and cell changes does to each cell,
+/// keyed by the offset from the loop cell. Bodies that do anything else or end on another cell
+/// than they started on have no such summary.
+pub(crate) fn pointer_neutral_deltas(body: &[Instruction]) -> Option<BTreeMap<isize, u32>> {
+	let mut deltas = BTreeMap::new();
+	let mut offset: isize = 0;
+	for instruction in body {
+		let (movement, delta) = loop_step(instruction)?;
+		offset = offset.checked_add(movement)?;
+		let cell_delta: &mut u32 = deltas.entry(offset).or_default();
+		*cell_delta = cell_delta.wrapping_add(delta);
+	}
+	if offset != 0 {
+		return None;
+	}
+	deltas.retain(|_, delta| *delta != 0);
+	Some(deltas)
+}
+
+fn loop_deltas(body: &[Instruction]) -> Option<BTreeMap<isize, u32>> {
+	let mut deltas = pointer_neutral_deltas(body)?;
+	if deltas.remove(&0) != Some(u32::MAX) {
+		return None;
+	}
+	Some(deltas)
+}
+
+pub fn multiply_loops(bytecode: Bytecode) -> Bytecode {
+	let mut optimized = Vec::with_capacity(bytecode.len());
+	let mut i = 0;
+	while i < bytecode.len() {
+		let source_offset = bytecode.source_offsets[i];
+		if let Instruction::OpenBracket { jump_location } = bytecode.instructions[i] {
+			if let Some(deltas) = loop_deltas(&bytecode.instructions[i + 1..jump_location]) {
+				for (offset, factor) in deltas {
+					optimized.push((Instruction::MulAdd { offset, factor }, source_offset));
+				}
+				optimized.push((Instruction::SetZero, source_offset));
+				i = jump_location + 1;
+				continue;
+			}
+		}
+		optimized.push((bytecode.instructions[i].clone(), source_offset));
+		i += 1;
+	}
+	optimized.into_iter().collect()
+}
+
+/// A loop right at the start is kept, since a machine may start the program on a tape seeded
+/// with [`Machine::with_memory`](crate::Machine::with_memory) or a snapshot.
+fn cell_is_zero(previous: Option<&(Instruction, usize)>) -> bool {
+	matches!(
+		previous,
+		Some((Instruction::SetZero | Instruction::CloseBracket { .. }, _))
+	)
+}
+
+pub fn dead_loops(bytecode: Bytecode) -> Bytecode {
+	let mut optimized: Vec<(Instruction, usize)> = Vec::with_capacity(bytecode.len());
+	let mut i = 0;
+	while i < bytecode.len() {
+		if let Instruction::OpenBracket { jump_location } = bytecode.instructions[i] {
+			if cell_is_zero(optimized.last()) {
+				i = jump_location + 1;
+				continue;
+			}
+		}
+		optimized.push((bytecode.instructions[i].clone(), bytecode.source_offsets[i]));
+		i += 1;
+	}
+	optimized.into_iter().collect()
+}
+
+/// Moves the straight-line code at the start of a program into the bytecode's initial tape,
+/// stopping at the first loop, I/O or other instruction whose effect depends on the tape, and at
+/// the first move left of the starting cell. A program made only of such code is left alone, so
+/// there is always an instruction to run once the initial tape is in place.
+pub fn fold_constants(bytecode: Bytecode) -> Bytecode {
+	let mut initial_tape: Vec<u32> = Vec::new();
+	let mut position = 0usize;
+	let mut folded = 0;
+	for instruction in bytecode.instructions() {
+		let Some((movement, delta)) = loop_step(instruction) else {
+			break;
+		};
+		let Some(next_position) = position.checked_add_signed(movement) else {
+			break;
+		};
+		position = next_position;
+		if delta != 0 {
+			if position >= initial_tape.len() {
+				initial_tape.resize(position + 1, 0);
+			}
+			initial_tape[position] = initial_tape[position].wrapping_add(delta);
+		}
+		folded += 1;
+	}
+	if folded == 0 || folded == bytecode.len() {
+		return bytecode;
+	}
+	let mut bytecode: Bytecode = bytecode.into_iter().skip(folded).collect();
+	bytecode.initial_tape = initial_tape;
+	bytecode.initial_data_pointer = position;
+	bytecode
+}
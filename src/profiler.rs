@@ -0,0 +1,112 @@
+use crate::{
+	compiler::{Bytecode, Instruction},
+	config::Config,
+	interpreter::{self, Machine, RuntimeError, StepOutcome},
+};
+use std::{
+	collections::BTreeMap,
+	fmt,
+	io::{Read, Write},
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Profile {
+	pub total_steps: u64,
+	pub loop_iterations: u64,
+	pub peak_tape_cells: usize,
+	pub instruction_counts: BTreeMap<&'static str, u64>,
+	/// How many times each instruction was executed, by its index in the bytecode.
+	pub execution_counts: Vec<u64>,
+}
+
+impl fmt::Display for Profile {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Total steps: {}", self.total_steps)?;
+		writeln!(f, "Loop iterations: {}", self.loop_iterations)?;
+		writeln!(f, "Peak tape cells: {}", self.peak_tape_cells)?;
+		for (mnemonic, count) in &self.instruction_counts {
+			writeln!(f, "{:<18}{}", mnemonic, count)?;
+		}
+		Ok(())
+	}
+}
+
+/// Executes compiled bytecode like [`execute`](crate::execute) and counts the executed instructions,
+/// the number of times a loop body was entered and the number of tape cells in use at the end.
+///
+/// ```
+/// use brainfuck::{compile, profile, Config};
+///
+/// let bytecode = compile(String::from("++[>+<-.]")).unwrap();
+/// let profile = profile(&bytecode, &mut &b""[..], &mut Vec::new(), &Config::default()).unwrap();
+/// assert_eq!(profile.total_steps, 15);
+/// assert_eq!(profile.loop_iterations, 2);
+/// assert_eq!(profile.peak_tape_cells, 2);
+/// assert_eq!(profile.instruction_counts["OpenBracket"], 2);
+/// assert_eq!(profile.instruction_counts["CloseBracket"], 2);
+/// ```
+pub fn profile(
+	bytecode: &Bytecode,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+	config: &Config,
+) -> Result<Profile, RuntimeError> {
+	let (profile, result) = profile_partial(bytecode, input, output, config);
+	result.map(|()| profile)
+}
+
+/// Like [`profile`], but also returns the profile of the steps executed before a runtime error,
+/// for example to report progress after an interrupt.
+///
+/// ```
+/// use brainfuck::{compile, profile_partial, Config, RuntimeErrorKind};
+///
+/// let bytecode = compile(String::from("+[]")).unwrap();
+/// let config = Config {
+///     max_steps: Some(100),
+///     ..Config::default()
+/// };
+/// let (profile, result) = profile_partial(&bytecode, &mut &b""[..], &mut Vec::new(), &config);
+/// assert!(matches!(result.unwrap_err().kind, RuntimeErrorKind::StepLimitExceeded));
+/// assert_eq!(profile.total_steps, 100);
+/// ```
+pub fn profile_partial(
+	bytecode: &Bytecode,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+	config: &Config,
+) -> (Profile, Result<(), RuntimeError>) {
+	let mut profile = Profile {
+		execution_counts: vec![0; bytecode.len()],
+		..Profile::default()
+	};
+	let mut output = interpreter::buffer_output(output, config.buffer_mode);
+	let mut machine = Machine::new(bytecode, input, &mut output, config);
+	let mut result = Ok(());
+	while let Some(instruction) = machine.current_instruction() {
+		let is_open_bracket = matches!(instruction, Instruction::OpenBracket { .. });
+		let instruction_pointer = machine.instruction_pointer();
+		let outcome = match machine.step() {
+			Ok(outcome) => outcome,
+			Err(error) => {
+				result = Err(error);
+				break;
+			}
+		};
+		profile.total_steps += 1;
+		profile.execution_counts[instruction_pointer] += 1;
+		*profile
+			.instruction_counts
+			.entry(instruction.mnemonic())
+			.or_insert(0) += 1;
+		if is_open_bracket && machine.instruction_pointer() == instruction_pointer + 1 {
+			profile.loop_iterations += 1;
+		}
+		if outcome == StepOutcome::Halted {
+			break;
+		}
+	}
+	profile.peak_tape_cells = machine.memory().len();
+	let flushed = interpreter::flush_output(bytecode, &mut output);
+	(profile, result.and(flushed))
+}
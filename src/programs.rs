@@ -0,0 +1,30 @@
+//! Brainfuck programs bundled with the library, for demos and tests.
+//!
+//! ```
+//! use brainfuck::{programs, run_str};
+//!
+//! let mut output = Vec::new();
+//! run_str(programs::HELLO_WORLD, &mut &b""[..], &mut output).unwrap();
+//! assert_eq!(output, b"Hello World!\n");
+//! ```
+
+/// Prints "Hello World!".
+pub const HELLO_WORLD: &str = include_str!("../samples/hello_world.bf");
+
+/// Copies its input to its output. It stops at the end of input only if `,` then stores 0, see
+/// [`EofMode::Zero`](crate::EofMode::Zero).
+pub const CAT: &str = include_str!("../samples/cat.bf");
+
+/// Applies ROT13 to its input until the end of input.
+pub const ROT13: &str = include_str!("../samples/rot13.bf");
+
+/// Prints the first eleven Fibonacci numbers, separated by commas.
+pub const FIBONACCI: &str = include_str!("../samples/fibonacci.bf");
+
+/// All bundled programs with their names.
+pub const ALL: [(&str, &str); 4] = [
+	("hello_world", HELLO_WORLD),
+	("cat", CAT),
+	("rot13", ROT13),
+	("fibonacci", FIBONACCI),
+];
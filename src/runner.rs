@@ -0,0 +1,105 @@
+use crate::{
+	compiler::{self, CompileError},
+	config::Config,
+	interpreter::{self, RuntimeError},
+};
+use std::io::{Cursor, Read, Write};
+use thiserror::Error;
+
+/// A compile or runtime error, displayed the same way as the error it wraps so `?` can pass
+/// either kind on.
+#[derive(Error, Debug)]
+pub enum BfError {
+	#[error(transparent)]
+	Compile(#[from] CompileError),
+	#[error(transparent)]
+	Runtime(#[from] RuntimeError),
+}
+
+/// Compiles and executes Brainfuck source code in one go, using the default [`Config`].
+///
+/// ```
+/// use brainfuck::run_str;
+///
+/// let source_code = include_str!("../samples/hello_world.bf");
+/// let mut output = Vec::new();
+/// run_str(source_code, &mut &b""[..], &mut output).unwrap();
+/// assert_eq!(String::from_utf8(output).unwrap(), "Hello World!\n");
+/// ```
+pub fn run_str(
+	source_code: &str,
+	input: &mut dyn Read,
+	output: &mut dyn Write,
+) -> Result<(), BfError> {
+	let config = Config::default();
+	let bytecode = compiler::compile_with_config(source_code, &config)?;
+	interpreter::execute(&bytecode, input, output, &config)?;
+	Ok(())
+}
+
+/// Splits source code at its first `!` into the program and the input it should read, the way
+/// some online judges bundle both in one file. A leading `#!` line is not searched. Without a
+/// `!` the input is empty.
+///
+/// ```
+/// use brainfuck::{execute_bounded, split_program_input};
+///
+/// let (program, input) = split_program_input(b",.!\xff");
+/// assert_eq!((program, input), (&b",."[..], &b"\xff"[..]));
+/// assert_eq!(execute_bounded(program, input, 100).unwrap(), b"\xff");
+/// ```
+pub fn split_program_input(source_code: &[u8]) -> (&[u8], &[u8]) {
+	let start = if source_code.starts_with(b"#!") {
+		source_code
+			.iter()
+			.position(|&byte| byte == b'\n')
+			.unwrap_or(source_code.len())
+	} else {
+		0
+	};
+	match source_code[start..].iter().position(|&byte| byte == b'!') {
+		Some(index) => (
+			&source_code[..start + index],
+			&source_code[start + index + 1..],
+		),
+		None => (source_code, &[]),
+	}
+}
+
+/// Compiles and executes Brainfuck source code on an in-memory input with the default
+/// [`Config`] and returns its output, the shortest way to run a program in a test.
+///
+/// ```
+/// use brainfuck::run_capture;
+///
+/// assert_eq!(run_capture(",+.", b"A").unwrap(), b"B");
+/// ```
+pub fn run_capture(source: &str, input: &[u8]) -> Result<Vec<u8>, BfError> {
+	let mut output = Vec::new();
+	run_str(source, &mut Cursor::new(input), &mut output)?;
+	Ok(output)
+}
+
+/// Compiles and executes raw Brainfuck source code on an in-memory input, stopping with a
+/// step limit error after `max_steps` instructions, and returns the output.
+///
+/// It never touches stdin or stdout and reports malformed programs as errors, which makes it a
+/// convenient fuzzing target. Source bytes that are not valid UTF-8 are treated as comments.
+///
+/// ```
+/// use brainfuck::{execute_bounded, BfError};
+///
+/// assert_eq!(execute_bounded(b",+.", b"A", 100).unwrap(), b"B");
+/// assert!(matches!(execute_bounded(b"+[]", b"", 100), Err(BfError::Runtime(_))));
+/// assert!(matches!(execute_bounded(b"\xff]", b"", 100), Err(BfError::Compile(_))));
+/// ```
+pub fn execute_bounded(source: &[u8], input: &[u8], max_steps: u64) -> Result<Vec<u8>, BfError> {
+	let config = Config {
+		max_steps: Some(max_steps),
+		..Config::default()
+	};
+	let bytecode = compiler::compile_with_config(source, &config)?;
+	let mut output = Vec::new();
+	interpreter::execute(&bytecode, &mut &input[..], &mut output, &config)?;
+	Ok(output)
+}
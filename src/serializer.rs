@@ -0,0 +1,191 @@
+use crate::compiler::{invalid_jump_location, Bytecode, Instruction};
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"BFBC";
+const VERSION: u8 = 3;
+
+#[derive(Error, Debug)]
+pub enum DeserializeError {
+	#[error("Bytecode error: Invalid header")]
+	InvalidHeader,
+	#[error("Bytecode error: Unexpected end of data")]
+	UnexpectedEnd,
+	#[error("Bytecode error: Malformed number")]
+	MalformedNumber,
+	#[error("Bytecode error: Unknown opcode {0}")]
+	UnknownOpcode(u8),
+	#[error("Bytecode error: Invalid jump location at instruction {0}")]
+	InvalidJumpLocation(usize),
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+	while value >= 0x80 {
+		bytes.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<u64, DeserializeError> {
+	let mut value = 0u64;
+	for shift in (0..64).step_by(7) {
+		let byte = bytes.next().ok_or(DeserializeError::UnexpectedEnd)?;
+		value |= u64::from(byte & 0x7f) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(DeserializeError::MalformedNumber)
+}
+
+fn zigzag(value: isize) -> u64 {
+	((value << 1) ^ (value >> (isize::BITS - 1))) as u64
+}
+
+fn unzigzag(value: u64) -> isize {
+	((value >> 1) as isize) ^ -((value & 1) as isize)
+}
+
+fn encode(instruction: &Instruction) -> (u8, Vec<u64>) {
+	match *instruction {
+		Instruction::IncPointer => (0, vec![]),
+		Instruction::DecPointer => (1, vec![]),
+		Instruction::IncByte => (2, vec![]),
+		Instruction::DecByte => (3, vec![]),
+		Instruction::AddPointer(amount) => (4, vec![amount as u64]),
+		Instruction::SubPointer(amount) => (5, vec![amount as u64]),
+		Instruction::AddByte(amount) => (6, vec![amount.into()]),
+		Instruction::SubByte(amount) => (7, vec![amount.into()]),
+		Instruction::SetZero => (8, vec![]),
+		Instruction::MulAdd { offset, factor } => (16, vec![zigzag(offset), factor.into()]),
+		Instruction::Debug => (9, vec![]),
+		Instruction::Halt => (17, vec![]),
+		Instruction::Output => (10, vec![]),
+		Instruction::Input => (11, vec![]),
+		Instruction::EmptyOpenBracket => (12, vec![]),
+		Instruction::EmptyCloseBracket => (13, vec![]),
+		Instruction::OpenBracket { jump_location } => (14, vec![jump_location as u64]),
+		Instruction::CloseBracket { jump_location } => (15, vec![jump_location as u64]),
+	}
+}
+
+fn decode(
+	opcode: u8,
+	bytes: &mut impl Iterator<Item = u8>,
+) -> Result<Instruction, DeserializeError> {
+	let mut operand = || read_varint(bytes);
+	Ok(match opcode {
+		0 => Instruction::IncPointer,
+		1 => Instruction::DecPointer,
+		2 => Instruction::IncByte,
+		3 => Instruction::DecByte,
+		4 => Instruction::AddPointer(operand()? as usize),
+		5 => Instruction::SubPointer(operand()? as usize),
+		6 => Instruction::AddByte(operand()? as u32),
+		7 => Instruction::SubByte(operand()? as u32),
+		8 => Instruction::SetZero,
+		9 => Instruction::Debug,
+		10 => Instruction::Output,
+		11 => Instruction::Input,
+		12 => Instruction::EmptyOpenBracket,
+		13 => Instruction::EmptyCloseBracket,
+		14 => Instruction::OpenBracket {
+			jump_location: operand()? as usize,
+		},
+		15 => Instruction::CloseBracket {
+			jump_location: operand()? as usize,
+		},
+		16 => Instruction::MulAdd {
+			offset: unzigzag(operand()?),
+			factor: operand()? as u32,
+		},
+		17 => Instruction::Halt,
+		opcode => return Err(DeserializeError::UnknownOpcode(opcode)),
+	})
+}
+
+/// Serializes bytecode, including jump locations and source offsets, into a compact binary format.
+///
+/// Source offsets and the line starts used to map them to lines and columns are stored as
+/// differences to the previous value to keep them small. Counted and fused instructions such as
+/// `AddByte(n)` and `MulAdd` are stored as they are, so an optimized program stays compact and
+/// loads without being optimized again. The initial tape follows the line starts.
+///
+/// ```
+/// use brainfuck::{compile, compile_with_config, deserialize, serialize, Config};
+///
+/// let bytecode = compile(String::from("++[>+<-]<[->>+<<]<[>]")).unwrap();
+/// assert_eq!(deserialize(&serialize(&bytecode)).unwrap(), bytecode);
+///
+/// let config = Config {
+///     fold_constants: true,
+///     ..Config::default()
+/// };
+/// let bytecode = compile_with_config("+>++>.", &config).unwrap();
+/// assert_eq!(deserialize(&serialize(&bytecode)).unwrap(), bytecode);
+/// ```
+pub fn serialize(bytecode: &Bytecode) -> Vec<u8> {
+	let mut bytes = MAGIC.to_vec();
+	bytes.push(VERSION);
+	write_varint(&mut bytes, bytecode.len() as u64);
+	let mut previous_offset = 0;
+	for (instruction, offset) in bytecode.iter() {
+		let (opcode, operands) = encode(instruction);
+		bytes.push(opcode);
+		for operand in operands {
+			write_varint(&mut bytes, operand);
+		}
+		write_varint(&mut bytes, offset.wrapping_sub(previous_offset) as u64);
+		previous_offset = offset;
+	}
+	write_varint(&mut bytes, bytecode.line_starts.len() as u64);
+	let mut previous_line_start = 0;
+	for &line_start in &bytecode.line_starts {
+		write_varint(
+			&mut bytes,
+			line_start.wrapping_sub(previous_line_start) as u64,
+		);
+		previous_line_start = line_start;
+	}
+	write_varint(&mut bytes, bytecode.initial_tape.len() as u64);
+	for &cell in &bytecode.initial_tape {
+		write_varint(&mut bytes, cell.into());
+	}
+	write_varint(&mut bytes, bytecode.initial_data_pointer as u64);
+	bytes
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<Bytecode, DeserializeError> {
+	let body = bytes
+		.strip_prefix(MAGIC.as_slice())
+		.and_then(|rest| rest.strip_prefix(&[VERSION]))
+		.ok_or(DeserializeError::InvalidHeader)?;
+	let mut bytes = body.iter().copied();
+	let len = read_varint(&mut bytes)?;
+	let mut offset = 0usize;
+	let mut bytecode = (0..len)
+		.map(|_| {
+			let opcode = bytes.next().ok_or(DeserializeError::UnexpectedEnd)?;
+			let instruction = decode(opcode, &mut bytes)?;
+			offset = offset.wrapping_add(read_varint(&mut bytes)? as usize);
+			Ok((instruction, offset))
+		})
+		.collect::<Result<Bytecode, DeserializeError>>()?;
+	let line_count = read_varint(&mut bytes)?;
+	let mut line_start = 0usize;
+	bytecode.line_starts = (0..line_count)
+		.map(|_| {
+			line_start = line_start.wrapping_add(read_varint(&mut bytes)? as usize);
+			Ok(line_start)
+		})
+		.collect::<Result<Vec<usize>, DeserializeError>>()?;
+	let cell_count = read_varint(&mut bytes)?;
+	bytecode.initial_tape = (0..cell_count)
+		.map(|_| Ok(read_varint(&mut bytes)? as u32))
+		.collect::<Result<Vec<u32>, DeserializeError>>()?;
+	bytecode.initial_data_pointer = read_varint(&mut bytes)? as usize;
+	if let Some(i) = invalid_jump_location(&bytecode) {
+		return Err(DeserializeError::InvalidJumpLocation(i));
+	}
+	Ok(bytecode)
+}
@@ -0,0 +1,235 @@
+use crate::compiler::{is_close_bracket, is_open_bracket, Bytecode, Instruction};
+
+fn c_statement(instruction: &Instruction) -> String {
+	match instruction {
+		Instruction::IncPointer => String::from("++p;"),
+		Instruction::DecPointer => String::from("--p;"),
+		Instruction::IncByte => String::from("++*p;"),
+		Instruction::DecByte => String::from("--*p;"),
+		Instruction::AddPointer(amount) => format!("p += {};", amount),
+		Instruction::SubPointer(amount) => format!("p -= {};", amount),
+		Instruction::AddByte(amount) => format!("*p += {};", amount),
+		Instruction::SubByte(amount) => format!("*p -= {};", amount),
+		Instruction::SetZero => String::from("*p = 0;"),
+		Instruction::MulAdd { offset, factor } => format!("p[{}] += *p * {}u;", offset, factor),
+		Instruction::Debug => String::from("/* # */"),
+		Instruction::Halt => String::from("return 0;"),
+		Instruction::Output => String::from("putchar(*p);"),
+		Instruction::Input => String::from("if ((c = getchar()) != EOF) *p = c;"),
+		Instruction::EmptyOpenBracket | Instruction::OpenBracket { .. } => {
+			String::from("while (*p) {")
+		}
+		Instruction::EmptyCloseBracket | Instruction::CloseBracket { .. } => String::from("}"),
+	}
+}
+
+fn rust_statement(instruction: &Instruction) -> String {
+	match instruction {
+		Instruction::IncPointer => String::from("p += 1;"),
+		Instruction::DecPointer => String::from("p -= 1;"),
+		Instruction::IncByte => String::from("tape[p] = tape[p].wrapping_add(1);"),
+		Instruction::DecByte => String::from("tape[p] = tape[p].wrapping_sub(1);"),
+		Instruction::AddPointer(amount) => format!("p += {};", amount),
+		Instruction::SubPointer(amount) => format!("p -= {};", amount),
+		Instruction::AddByte(amount) => {
+			format!("tape[p] = tape[p].wrapping_add({});", *amount as u8)
+		}
+		Instruction::SubByte(amount) => {
+			format!("tape[p] = tape[p].wrapping_sub({});", *amount as u8)
+		}
+		Instruction::SetZero => String::from("tape[p] = 0;"),
+		Instruction::MulAdd { offset, factor } => format!(
+			"tape[p.wrapping_add_signed({0})] = tape[p.wrapping_add_signed({0})].wrapping_add(tape[p].wrapping_mul({1}));",
+			offset,
+			*factor as u8
+		),
+		Instruction::Debug => String::from("// #"),
+		Instruction::Halt => String::from("output.flush().unwrap(); return;"),
+		Instruction::Output => String::from("output.write_all(&[tape[p]]).unwrap();"),
+		Instruction::Input => {
+			String::from("if input.read(&mut byte).unwrap() == 1 { tape[p] = byte[0]; }")
+		}
+		Instruction::EmptyOpenBracket | Instruction::OpenBracket { .. } => {
+			String::from("while tape[p] != 0 {")
+		}
+		Instruction::EmptyCloseBracket | Instruction::CloseBracket { .. } => String::from("}"),
+	}
+}
+
+fn wat_statement(instruction: &Instruction) -> String {
+	match instruction {
+		Instruction::IncPointer => {
+			String::from("(local.set $p (i32.add (local.get $p) (i32.const 1)))")
+		}
+		Instruction::DecPointer => {
+			String::from("(local.set $p (i32.sub (local.get $p) (i32.const 1)))")
+		}
+		Instruction::IncByte => String::from(
+			"(i32.store8 (local.get $p) (i32.add (i32.load8_u (local.get $p)) (i32.const 1)))",
+		),
+		Instruction::DecByte => String::from(
+			"(i32.store8 (local.get $p) (i32.sub (i32.load8_u (local.get $p)) (i32.const 1)))",
+		),
+		Instruction::AddPointer(amount) => format!(
+			"(local.set $p (i32.add (local.get $p) (i32.const {})))",
+			amount
+		),
+		Instruction::SubPointer(amount) => format!(
+			"(local.set $p (i32.sub (local.get $p) (i32.const {})))",
+			amount
+		),
+		Instruction::AddByte(amount) => format!(
+			"(i32.store8 (local.get $p) (i32.add (i32.load8_u (local.get $p)) (i32.const {})))",
+			*amount as u8
+		),
+		Instruction::SubByte(amount) => format!(
+			"(i32.store8 (local.get $p) (i32.sub (i32.load8_u (local.get $p)) (i32.const {})))",
+			*amount as u8
+		),
+		Instruction::SetZero => String::from("(i32.store8 (local.get $p) (i32.const 0))"),
+		Instruction::MulAdd { offset, factor } => format!(
+			"(i32.store8 (i32.add (local.get $p) (i32.const {0})) (i32.add (i32.load8_u (i32.add (local.get $p) (i32.const {0}))) (i32.mul (i32.load8_u (local.get $p)) (i32.const {1}))))",
+			offset,
+			*factor as u8
+		),
+		Instruction::Debug => String::from(";; #"),
+		Instruction::Halt => String::from("(return)"),
+		Instruction::Output => String::from("(call $putchar (i32.load8_u (local.get $p)))"),
+		Instruction::Input => String::from(
+			"(local.set $c (call $getchar)) (if (i32.ge_s (local.get $c) (i32.const 0)) (then (i32.store8 (local.get $p) (local.get $c))))",
+		),
+		Instruction::EmptyOpenBracket | Instruction::OpenBracket { .. } => {
+			String::from("(block (loop (br_if 1 (i32.eqz (i32.load8_u (local.get $p))))")
+		}
+		Instruction::EmptyCloseBracket | Instruction::CloseBracket { .. } => {
+			String::from("(br 0)))")
+		}
+	}
+}
+
+fn transpile_body(
+	bytecode: &Bytecode,
+	statement: fn(&Instruction) -> String,
+	mut depth: usize,
+) -> String {
+	let mut body = String::new();
+	for instruction in bytecode.instructions() {
+		if is_close_bracket(instruction) {
+			depth -= 1;
+		}
+		body.push_str(&"\t".repeat(depth));
+		body.push_str(&statement(instruction));
+		body.push('\n');
+		if is_open_bracket(instruction) {
+			depth += 1;
+		}
+	}
+	body
+}
+
+/// Lists the cells of the initial tape as bytes, separated by `, `.
+fn initial_bytes(bytecode: &Bytecode) -> String {
+	bytecode
+		.initial_tape()
+		.iter()
+		.map(|&cell| (cell as u8).to_string())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Transpiles bytecode into a standalone C program with a 30000 cell tape.
+///
+/// ```
+/// use brainfuck::{compile, compile_with_config, transpile_c, Config};
+///
+/// let c_source = transpile_c(&compile(String::from("+.")).unwrap());
+/// assert!(c_source.contains("*p += 1;"));
+/// assert!(c_source.contains("putchar(*p);"));
+///
+/// let config = Config {
+///     fold_constants: true,
+///     ..Config::default()
+/// };
+/// let c_source = transpile_c(&compile_with_config("+>++.", &config).unwrap());
+/// assert!(c_source.contains("unsigned char tape[30000] = {1, 2};"));
+/// assert!(c_source.contains("unsigned char *p = tape + 1;"));
+/// ```
+pub fn transpile_c(bytecode: &Bytecode) -> String {
+	let tape = match bytecode.initial_tape() {
+		[] => String::new(),
+		_ => format!(" = {{{}}}", initial_bytes(bytecode)),
+	};
+	let data_pointer = match bytecode.initial_data_pointer() {
+		0 => String::new(),
+		data_pointer => format!(" + {}", data_pointer),
+	};
+	format!(
+		"#include <stdio.h>\n\nunsigned char tape[30000]{};\n\nint main(void) {{\n\tunsigned char *p = tape{};\n\tint c;\n{}\treturn 0;\n}}\n",
+		tape,
+		data_pointer,
+		transpile_body(bytecode, c_statement, 1)
+	)
+}
+
+/// Transpiles bytecode into a standalone Rust program with a 30000 cell tape.
+///
+/// ```
+/// use brainfuck::{compile, transpile_rust};
+///
+/// let rust_source = transpile_rust(&compile(String::from("+.")).unwrap());
+/// assert!(rust_source.contains("tape[p] = tape[p].wrapping_add(1);"));
+/// assert!(rust_source.contains("output.write_all(&[tape[p]]).unwrap();"));
+/// ```
+pub fn transpile_rust(bytecode: &Bytecode) -> String {
+	let tape = match bytecode.initial_tape() {
+		[] => String::new(),
+		cells => format!(
+			"\ttape[..{}].copy_from_slice(&[{}]);\n",
+			cells.len(),
+			initial_bytes(bytecode)
+		),
+	};
+	format!(
+		"#![allow(unused)]\n\nuse std::io::{{self, Read, Write}};\n\nfn main() {{\n\tlet mut tape = vec![0u8; 30000];\n{}\tlet mut p: usize = {};\n\tlet mut byte = [0u8];\n\tlet mut input = io::stdin().lock();\n\tlet mut output = io::stdout().lock();\n{}\toutput.flush().unwrap();\n}}\n",
+		tape,
+		bytecode.initial_data_pointer(),
+		transpile_body(bytecode, rust_statement, 1)
+	)
+}
+
+/// Transpiles bytecode into a WebAssembly text module that can be assembled with `wat2wasm`.
+///
+/// The tape is the module's exported linear memory of one 64 KiB page. The exported `run`
+/// function executes the program, writing each output byte with the imported `env.putchar` and
+/// reading input with `env.getchar`, which should return a negative value at the end of input.
+/// An initial tape becomes a data segment at the start of the memory.
+///
+/// ```
+/// use brainfuck::{compile, transpile_wat};
+///
+/// let wat_source = transpile_wat(&compile(String::from("+[.-]")).unwrap());
+/// assert!(wat_source.contains("(memory (export \"memory\") 1)"));
+/// assert!(wat_source.contains("(block (loop (br_if 1"));
+/// ```
+pub fn transpile_wat(bytecode: &Bytecode) -> String {
+	let data = match bytecode.initial_tape() {
+		[] => String::new(),
+		cells => {
+			let bytes: String = cells
+				.iter()
+				.map(|&cell| format!("\\{:02x}", cell as u8))
+				.collect();
+			format!("\t(data (i32.const 0) \"{}\")\n", bytes)
+		}
+	};
+	let data_pointer = match bytecode.initial_data_pointer() {
+		0 => String::new(),
+		data_pointer => format!("\t\t(local.set $p (i32.const {}))\n", data_pointer),
+	};
+	format!(
+		"(module\n\t(import \"env\" \"putchar\" (func $putchar (param i32)))\n\t(import \"env\" \"getchar\" (func $getchar (result i32)))\n\t(memory (export \"memory\") 1)\n{}\t(func (export \"run\")\n\t\t(local $p i32)\n\t\t(local $c i32)\n{}{}\t)\n)\n",
+		data,
+		data_pointer,
+		transpile_body(bytecode, wat_statement, 2)
+	)
+}
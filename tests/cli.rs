@@ -0,0 +1,263 @@
+use std::{
+	fs,
+	io::Write,
+	path::Path,
+	process::{Command, Output, Stdio},
+	thread,
+	time::Duration,
+};
+
+fn fixture(name: &str) -> String {
+	Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures")
+		.join(name)
+		.to_string_lossy()
+		.into_owned()
+}
+
+fn brainfuck(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+		.args(args)
+		.output()
+		.unwrap()
+}
+
+fn brainfuck_with_stdin(args: &[&str], stdin: &[u8]) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(stdin).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+#[test]
+fn input_from_file() {
+	let path = fixture("input.txt");
+	let output = brainfuck(&["--eval", ",[.[-],]", "--input", &path]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, fs::read(&path).unwrap());
+}
+
+#[test]
+fn echo_input_copies_consumed_input_to_stderr() {
+	let output = brainfuck(&[
+		"--echo-input",
+		"--eval",
+		",,[-]+++.",
+		"--input",
+		&fixture("echo.txt"),
+	]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, [3]);
+	assert_eq!(output.stderr, b"xy");
+}
+
+#[test]
+fn max_output_stops_an_endless_printer() {
+	let output = brainfuck(&["--max-output", "5", "--eval", "+[.]"]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(output.stdout, [1; 5]);
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"Runtime error: Output limit exceeded at offset 2\n"
+	);
+}
+
+#[test]
+fn split_input_reads_input_after_the_first_bang() {
+	let output = brainfuck(&["--split-input", "--eval", ",.!A"]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"A");
+}
+
+#[test]
+fn split_input_keeps_bytes_that_are_not_utf8() {
+	let output = brainfuck_with_stdin(&["--split-input", "-"], b",.!\xff");
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"\xff");
+}
+
+#[test]
+fn log_output_names_the_cell_of_every_output_byte() {
+	let output = brainfuck(&[
+		"--log-output",
+		"--eval",
+		"++++++++[>++++++++<-]>+.>>++++++++[<++++++++>-]<++.",
+	]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"AB");
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"[cell 1] = 65 -> 'A'\n[cell 2] = 66 -> 'B'\n"
+	);
+}
+
+#[test]
+fn repl_keeps_the_tape_between_lines() {
+	let output = brainfuck_with_stdin(&["repl"], b"+++\n.\n");
+	assert!(output.status.success());
+	assert_eq!(output.stdout, [3]);
+
+	let output = brainfuck_with_stdin(&["repl"], b"+++\n:reset\n.\n:dump\n");
+	assert!(output.status.success());
+	assert_eq!(output.stdout, [0]);
+	assert_eq!(output.stderr, b"Data pointer: 0\n");
+
+	let output = brainfuck_with_stdin(&["repl"], b"+++\n[>++<-]>.\n");
+	assert!(output.status.success());
+	assert_eq!(output.stdout, [6]);
+}
+
+#[test]
+fn hot_listing_counts_loop_body_executions() {
+	let output = brainfuck(&["--hot", "--eval", "+++[>.<-]"]);
+	assert!(output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	let counts: Vec<u64> = stderr
+		.lines()
+		.map(|line| line.split_whitespace().next().unwrap().parse().unwrap())
+		.collect();
+	assert_eq!(counts[0], 1);
+	assert!(counts[2..counts.len() - 1].iter().all(|&count| count > 1));
+}
+
+#[test]
+fn no_input_fails_on_read() {
+	let output = brainfuck(&["--no-input", "--eval", "+.,"]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(output.stdout, [1]);
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"Runtime error: Input requested but none allowed at offset 2\n"
+	);
+}
+
+#[test]
+fn shebang_line_is_ignored() {
+	let output = brainfuck(&["--debug", &fixture("shebang.bf")]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"A");
+	assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn run_is_the_default_subcommand() {
+	let path = concat!(env!("CARGO_MANIFEST_DIR"), "/samples/hello_world.bf");
+	for args in [&["run", path][..], &[path][..]] {
+		let output = brainfuck(args);
+		assert!(output.status.success());
+		assert_eq!(output.stdout, b"Hello World!\n");
+	}
+}
+
+#[test]
+fn emit_subcommand() {
+	let output = brainfuck(&["emit", "c", "--eval", "+."]);
+	assert!(output.status.success());
+	assert!(String::from_utf8(output.stdout)
+		.unwrap()
+		.contains("putchar(*p);"));
+}
+
+#[test]
+fn disasm_subcommand() {
+	let output = brainfuck(&["disasm", "--eval", "+."]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"0000 AddByte 1\n0001 Output\n");
+}
+
+#[test]
+fn compile_subcommand() {
+	let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("compile_subcommand.bfc");
+	let path = path.to_str().unwrap();
+	let output = brainfuck(&["compile", "--eval", "++++++++[>++++++++<-]>+.", "-o", path]);
+	assert!(output.status.success());
+	let output = brainfuck(&["--run-bytecode", path]);
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"A");
+}
+
+#[test]
+fn verbose_prints_instruction_counts_per_stage() {
+	let output = brainfuck(&["disasm", "--verbose", "--eval", "+++[-]>[-]"]);
+	assert!(output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	let count = |stage: &str| {
+		stderr
+			.lines()
+			.find_map(|line| line.strip_prefix(&format!("{}: ", stage)))
+			.and_then(|rest| rest.strip_suffix(" instructions"))
+			.unwrap()
+			.parse::<usize>()
+			.unwrap()
+	};
+	assert_eq!(count("parse"), 10);
+	assert_eq!(count("match_brackets"), 10);
+	assert_eq!(count("fold_runs"), 8);
+	assert_eq!(count("clear_loops"), 4);
+}
+
+#[cfg(unix)]
+#[test]
+fn memory_preview_stops_at_the_first_interrupt() {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+		.args(["--memory-preview", "--eval", "+[]"])
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	thread::sleep(Duration::from_millis(500));
+	let killed = Command::new("kill")
+		.args(["-INT", &child.id().to_string()])
+		.status()
+		.unwrap();
+	assert!(killed.success());
+	for _ in 0..50 {
+		if child.try_wait().unwrap().is_some() {
+			break;
+		}
+		thread::sleep(Duration::from_millis(100));
+	}
+	if child.try_wait().unwrap().is_none() {
+		child.kill().unwrap();
+		panic!("the preview kept running after Ctrl-C");
+	}
+	let output = child.wait_with_output().unwrap();
+	assert_eq!(output.status.code(), Some(130));
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.contains("Runtime error: Interrupted at offset"));
+}
+
+#[test]
+fn memory_preview_marks_the_data_pointer() {
+	let output = brainfuck(&[
+		"--memory-preview",
+		"--preview-rate",
+		"1000",
+		"--eval",
+		"+>++.",
+	]);
+	assert!(output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	let frame = stderr.rsplit("\x1b[2J\x1b[H").next().unwrap();
+	let lines: Vec<&str> = frame.lines().collect();
+	let marker = lines[2].find('^').unwrap();
+	assert_eq!(&lines[0][marker..=marker], "1");
+	assert_eq!(&lines[1][marker..=marker], "2");
+	assert_eq!(output.stdout, [2]);
+}
+
+#[test]
+fn trace_logs_every_executed_instruction() {
+	let output = brainfuck(&["--trace", "--no-optimize", "--eval", "++"]);
+	assert!(output.status.success());
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"0000 IncByte dp 0 cell 1\n0001 IncByte dp 0 cell 2\n"
+	);
+}
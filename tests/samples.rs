@@ -0,0 +1,839 @@
+use brainfuck::{
+	analyze_pointer_range, assemble, code_density, compile, compile_str, compile_with_config,
+	deserialize, diagnose, diagnose_with_config, disassemble, execute, execute_with_error_output,
+	jump_table, lint, profile_partial, programs, run_capture, serialize, BfError, BufferMode,
+	CharMap, CompileErrorKind, Config, EofMode, Instruction, LeftBoundary, Machine, OptLevel,
+	OutputMode, RunOutcome, RuntimeErrorKind, TapeMode, WarningKind,
+};
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	io::{self, Read},
+	rc::Rc,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+fn run(source_code: &str, input: &[u8], config: &Config) -> Result<Vec<u8>, BfError> {
+	let bytecode = compile(String::from(source_code))?;
+	let mut output = Vec::new();
+	execute(&bytecode, &mut &input[..], &mut output, config)?;
+	Ok(output)
+}
+
+fn with_eof_mode(eof_mode: EofMode) -> Config {
+	Config {
+		eof_mode,
+		max_steps: Some(1_000_000),
+		..Config::default()
+	}
+}
+
+#[test]
+fn bundled_programs_compile() {
+	for (name, source_code) in programs::ALL {
+		assert!(
+			compile_str(source_code).is_ok(),
+			"{} failed to compile",
+			name
+		);
+	}
+}
+
+#[test]
+fn fibonacci() {
+	let output = run(programs::FIBONACCI, b"", &Config::default());
+	assert_eq!(output.unwrap(), b"1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89");
+}
+
+#[test]
+fn jump_table_is_symmetric() {
+	let bytecode = compile(String::from(include_str!("../samples/mandelbrot.bf"))).unwrap();
+	let table = jump_table(bytecode.instructions()).unwrap();
+	assert!(table.iter().any(Option::is_some));
+	for (i, jump_location) in table.iter().enumerate() {
+		if let Some(jump_location) = *jump_location {
+			assert_eq!(table[jump_location], Some(i));
+		}
+	}
+}
+
+#[test]
+fn jump_locations_survive_optimization() {
+	let source_code = "++++[>+++[-]>+++[<++>-]<<-]>[-][+]>++[>+<-]>[<<+>>-]<<.";
+	let bytecode = compile(String::from(source_code)).unwrap();
+	let unoptimized = compile_with_config(
+		String::from(source_code),
+		&Config {
+			opt_level: OptLevel::None,
+			..Config::default()
+		},
+	)
+	.unwrap();
+	assert!(bytecode.len() < unoptimized.len());
+	let instructions = bytecode.instructions();
+	for (i, instruction) in instructions.iter().enumerate() {
+		match *instruction {
+			Instruction::OpenBracket { jump_location } => assert_eq!(
+				instructions[jump_location],
+				Instruction::CloseBracket { jump_location: i }
+			),
+			Instruction::CloseBracket { jump_location } => assert_eq!(
+				instructions[jump_location],
+				Instruction::OpenBracket { jump_location: i }
+			),
+			_ => {}
+		}
+	}
+	let run = |bytecode| {
+		let mut output = Vec::new();
+		execute(bytecode, &mut &b""[..], &mut output, &Config::default()).unwrap();
+		output
+	};
+	assert_eq!(run(&bytecode), [2]);
+	assert_eq!(run(&unoptimized), [2]);
+}
+
+#[test]
+fn multiply_loops_match_the_unoptimized_loop() {
+	let run = |source_code: &str, config: &Config| {
+		let bytecode = compile_with_config(source_code, config).unwrap();
+		let (mut input, mut output) = (&b""[..], Vec::new());
+		let mut machine = Machine::new(&bytecode, &mut input, &mut output, config);
+		machine.run().unwrap();
+		let memory = machine.memory().to_vec();
+		drop(machine);
+		(memory, output)
+	};
+	let unoptimized = |config: &Config| Config {
+		opt_level: OptLevel::None,
+		..config.clone()
+	};
+	let source_code = "+++++[->++<]>.";
+	assert_eq!(
+		run(source_code, &Config::default()),
+		run(source_code, &unoptimized(&Config::default()))
+	);
+	assert_eq!(run(source_code, &Config::default()).1, [10]);
+	// On a tape of two cells the `>>` lands back on the loop cell, which then runs 255 times.
+	let config = Config {
+		tape_mode: TapeMode::Wrapping(2),
+		..Config::default()
+	};
+	let source_code = "+[->+>++<<]>.";
+	assert_eq!(
+		run(source_code, &config),
+		run(source_code, &unoptimized(&config))
+	);
+	assert_eq!(run(source_code, &config).1, [255]);
+}
+
+#[test]
+fn code_after_a_top_level_halt_is_unreachable() {
+	let config = Config {
+		halt: true,
+		..Config::default()
+	};
+	let lint_source =
+		|source_code: &str| lint(&compile_with_config(String::from(source_code), &config).unwrap());
+	let warnings = lint_source("+@++");
+	assert_eq!(warnings.len(), 1);
+	assert_eq!(warnings[0].kind, WarningKind::Unreachable);
+	assert_eq!(warnings[0].offset, 2);
+	assert!(lint_source("+[@]+").is_empty());
+}
+
+#[test]
+fn loops_that_never_change_their_cell_are_infinite() {
+	let unoptimized = Config {
+		opt_level: OptLevel::None,
+		..Config::default()
+	};
+	for (source_code, config) in [
+		("+[>+<]", Config::default()),
+		("+[<>]", unoptimized.clone()),
+		("+[]", unoptimized.clone()),
+	] {
+		let warnings = lint(&compile_with_config(source_code, &config).unwrap());
+		assert_eq!(warnings.len(), 1, "{source_code}");
+		assert_eq!(warnings[0].kind, WarningKind::InfiniteLoop);
+		assert_eq!(warnings[0].offset, 1);
+	}
+	for source_code in ["+[>+<-]", "+[>]", "+[.]", "+[>+<[-]]"] {
+		let warnings = lint(&compile_with_config(source_code, &unoptimized).unwrap());
+		assert!(warnings.is_empty(), "{source_code}");
+	}
+}
+
+#[test]
+fn diagnostics_cover_unmatched_brackets() {
+	let diagnostics = diagnose("é[+\n]]");
+	assert_eq!(diagnostics.len(), 1);
+	assert_eq!(diagnostics[0].range, 6..7);
+	assert_eq!(diagnostics[0].message, "Unmatched ']'");
+
+	let diagnostics = diagnose("[[-]");
+	assert_eq!(diagnostics.len(), 1);
+	assert_eq!(diagnostics[0].range, 0..1);
+	assert_eq!(diagnostics[0].message, "Unmatched '['");
+
+	let config = Config {
+		strict: true,
+		..Config::default()
+	};
+	let diagnostics = diagnose_with_config("]+x", &config);
+	let ranges: Vec<_> = diagnostics.iter().map(|d| d.range.clone()).collect();
+	assert_eq!(ranges, [0..1, 2..3]);
+	assert!(diagnose("+[-]").is_empty());
+}
+
+#[test]
+fn runs_of_commands_compile_densely() {
+	let source_code = "+".repeat(200) + ">" + &"-".repeat(100) + "<" + &"+".repeat(55) + ".";
+	let (characters, raw, optimized) = code_density(&source_code, &Config::default()).unwrap();
+	assert_eq!(characters, 358);
+	assert_eq!(raw, 358);
+	assert_eq!(optimized, 6);
+}
+
+#[test]
+fn pointer_range() {
+	let range = |source_code: &str| analyze_pointer_range(&compile_str(source_code).unwrap());
+	assert_eq!(range(">>><"), Some((0, 3)));
+	assert_eq!(range("<<+[->>>+<<<]"), Some((-2, 1)));
+	assert_eq!(range("+[>]"), None);
+	let listing = "0000 AddPointer 9223372036854775807\n0001 AddPointer 9223372036854775807\n";
+	assert_eq!(analyze_pointer_range(&assemble(listing).unwrap()), None);
+}
+
+#[test]
+fn errors_display_through_bf_error() {
+	let error = run("+[", b"", &Config::default()).unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"Compile error: Unmatched '[' at line 1, column 2"
+	);
+	let config = Config {
+		max_steps: Some(10),
+		..Config::default()
+	};
+	let error = run("+[]", b"", &config).unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"Runtime error: Step limit exceeded at offset 2"
+	);
+}
+
+#[test]
+fn infinite_loop_hits_step_limit() {
+	let config = Config {
+		max_steps: Some(1000),
+		..Config::default()
+	};
+	match run("+[]", b"", &config) {
+		Err(BfError::Runtime(error)) => {
+			assert!(matches!(error.kind, RuntimeErrorKind::StepLimitExceeded));
+			assert_eq!(error.offset, 2);
+		}
+		other => panic!("expected a step limit error, got {other:?}"),
+	}
+}
+
+#[test]
+fn serialized_bytecode_keeps_counted_instructions() {
+	let source_code = "+".repeat(200) + ">" + &"-".repeat(100) + "[->+<]";
+	let bytecode = compile(source_code.clone()).unwrap();
+	let unoptimized = compile_with_config(
+		source_code,
+		&Config {
+			opt_level: OptLevel::None,
+			..Config::default()
+		},
+	)
+	.unwrap();
+	let bytes = serialize(&bytecode);
+	assert!(bytes.len() * 10 < serialize(&unoptimized).len());
+	let deserialized = deserialize(&bytes).unwrap();
+	assert_eq!(
+		deserialized.instructions(),
+		[
+			Instruction::AddByte(200),
+			Instruction::AddPointer(1),
+			Instruction::SubByte(100),
+			Instruction::MulAdd {
+				offset: 1,
+				factor: 1
+			},
+			Instruction::SetZero
+		]
+	);
+}
+
+#[test]
+fn close_bracket_before_its_open_bracket_is_rejected() {
+	let error = compile_str("+\n][").unwrap_err();
+	assert_eq!(
+		error.kind,
+		CompileErrorKind::UnmatchedCloseBracket { line: 2, column: 1 }
+	);
+	assert_eq!(error.span, Some(2..3));
+}
+
+#[test]
+fn stray_close_brackets_do_not_panic() {
+	for source_code in ["]", "]]][[["] {
+		let error = compile_str(source_code).unwrap_err();
+		assert_eq!(
+			error.kind,
+			CompileErrorKind::UnmatchedCloseBracket { line: 1, column: 1 }
+		);
+	}
+	let error = jump_table(&[
+		Instruction::EmptyCloseBracket,
+		Instruction::EmptyOpenBracket,
+	])
+	.unwrap_err();
+	assert_eq!(error.kind, CompileErrorKind::UnbalancedBrackets);
+}
+
+#[test]
+fn hello_world() {
+	let output = run(
+		include_str!("../samples/hello_world.bf"),
+		b"",
+		&Config::default(),
+	);
+	assert_eq!(output.unwrap(), b"Hello World!\n");
+}
+
+#[test]
+fn hello_world_captured() {
+	let output = run_capture(programs::HELLO_WORLD, b"").unwrap();
+	assert_eq!(String::from_utf8(output).unwrap(), "Hello World!\n");
+}
+
+#[test]
+fn custom_char_map() {
+	let config = Config {
+		char_map: CharMap::default()
+			.with('a', '+')
+			.with('👉', '>')
+			.with('🔁', '[')
+			.with('🔚', ']'),
+		..Config::default()
+	};
+	let run = |source_code: &str| {
+		let bytecode = compile_with_config(String::from(source_code), &config).unwrap();
+		let mut output = Vec::new();
+		execute(&bytecode, &mut &b""[..], &mut output, &config).unwrap();
+		output
+	};
+	assert_eq!(run("aaa."), [3]);
+	assert_eq!(run("aa🔁-👉aaa<🔚👉."), [6]);
+}
+
+#[test]
+fn echo_char() {
+	let output = run(
+		include_str!("../samples/echo_char.bf"),
+		b"xyz",
+		&Config::default(),
+	);
+	assert_eq!(output.unwrap(), b"x");
+}
+
+#[test]
+fn fully_buffered_output_is_flushed_on_return() {
+	struct Flushed {
+		pending: Vec<u8>,
+		flushed: Vec<u8>,
+	}
+
+	impl io::Write for Flushed {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.pending.extend_from_slice(buf);
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			self.flushed.append(&mut self.pending);
+			Ok(())
+		}
+	}
+
+	let config = Config {
+		buffer_mode: BufferMode::Full,
+		left_boundary: LeftBoundary::Error,
+		..Config::default()
+	};
+	for (source_code, succeeds) in [("+.+.", true), ("+.+.<", false)] {
+		let bytecode = compile(String::from(source_code)).unwrap();
+		let mut output = Flushed {
+			pending: Vec::new(),
+			flushed: Vec::new(),
+		};
+		let result = execute(&bytecode, &mut &b""[..], &mut output, &config);
+		assert_eq!(result.is_ok(), succeeds);
+		assert_eq!(output.flushed, [1, 2]);
+		assert!(output.pending.is_empty());
+	}
+}
+
+#[test]
+fn echo_three_bytes_from_a_slice() {
+	let bytecode = compile(String::from(",.>,.>,.")).unwrap();
+	let mut input: &[u8] = b"xyz";
+	let mut output = Vec::new();
+	execute(&bytecode, &mut input, &mut output, &Config::default()).unwrap();
+	assert_eq!(output, b"xyz");
+	assert!(input.is_empty());
+}
+
+#[test]
+fn echo_each_byte_then_hit_eof() {
+	let output = run(",.,.,.", b"ab\n", &Config::default());
+	assert_eq!(output.unwrap(), b"ab\n");
+	let output = run(",.,.,.,.", b"ab\n", &with_eof_mode(EofMode::Unchanged));
+	assert_eq!(output.unwrap(), b"ab\n\n");
+	let output = run(",.,.,.,.", b"ab\n", &with_eof_mode(EofMode::Zero));
+	assert_eq!(output.unwrap(), b"ab\n\0");
+	let output = run(",.,.,.,.", b"ab\n", &with_eof_mode(EofMode::Error));
+	assert!(matches!(
+		output,
+		Err(BfError::Runtime(error))
+			if matches!(error.kind, RuntimeErrorKind::EndOfInput { bytes_read: 3 })
+	));
+}
+
+#[test]
+fn cat_stops_at_zero_eof() {
+	let output = run(
+		include_str!("../samples/cat.bf"),
+		b"line one\nline two\n",
+		&with_eof_mode(EofMode::Zero),
+	);
+	assert_eq!(output.unwrap(), b"line one\nline two\n");
+}
+
+#[test]
+fn cat_never_stops_at_unchanged_eof() {
+	let output = run(
+		include_str!("../samples/cat.bf"),
+		b"abc",
+		&with_eof_mode(EofMode::Unchanged),
+	);
+	assert!(matches!(
+		output,
+		Err(BfError::Runtime(error)) if matches!(error.kind, RuntimeErrorKind::StepLimitExceeded)
+	));
+}
+
+#[test]
+fn cat_reports_eof_as_error() {
+	let output = run(
+		include_str!("../samples/cat.bf"),
+		b"abc",
+		&with_eof_mode(EofMode::Error),
+	);
+	assert!(matches!(
+		output,
+		Err(BfError::Runtime(error))
+			if matches!(error.kind, RuntimeErrorKind::EndOfInput { bytes_read: 3 })
+	));
+}
+
+#[test]
+fn cat_strips_input_newlines() {
+	let config = with_eof_mode(EofMode::Zero);
+	let output = run(include_str!("../samples/cat.bf"), b"hi\n", &config);
+	assert_eq!(output.unwrap(), b"hi\n");
+	let config = Config {
+		strip_input_newlines: true,
+		..config
+	};
+	let output = run(include_str!("../samples/cat.bf"), b"hi\n", &config);
+	assert_eq!(output.unwrap(), b"hi");
+}
+
+#[test]
+fn rot13_stops_at_unchanged_and_negative_one_eof() {
+	for eof_mode in [EofMode::Unchanged, EofMode::NegativeOne] {
+		let output = run(
+			include_str!("../samples/rot13.bf"),
+			b"Hello, World!\n",
+			&with_eof_mode(eof_mode),
+		);
+		assert_eq!(output.unwrap(), b"Uryyb, Jbeyq!\n");
+	}
+}
+
+#[test]
+fn adder() {
+	let output = run(
+		include_str!("../samples/adder.bf"),
+		b"34",
+		&Config::default(),
+	);
+	assert_eq!(output.unwrap(), b"7");
+}
+
+#[test]
+fn wrapping_cells() {
+	let output = run(
+		include_str!("../samples/wrapping_cells.bf"),
+		b"",
+		&Config::default(),
+	);
+	assert_eq!(output.unwrap(), b"AA");
+}
+
+#[test]
+fn exceeding_memory() {
+	let config = Config {
+		max_tape_cells: Some(1000),
+		..Config::default()
+	};
+	let output = run(include_str!("../samples/exceeding_memory.bf"), b"", &config);
+	assert!(matches!(
+		output,
+		Err(BfError::Runtime(error)) if matches!(error.kind, RuntimeErrorKind::TapeLimitExceeded)
+	));
+}
+
+#[test]
+fn assembled_listing_runs_identically() {
+	let bytecode = compile(String::from(include_str!("../samples/rot13.bf"))).unwrap();
+	let assembled = assemble(&disassemble(&bytecode)).unwrap();
+	let input = b"Hello, World!\n";
+	let mut expected = Vec::new();
+	execute(
+		&bytecode,
+		&mut &input[..],
+		&mut expected,
+		&Config::default(),
+	)
+	.unwrap();
+	let mut output = Vec::new();
+	execute(&assembled, &mut &input[..], &mut output, &Config::default()).unwrap();
+	assert_eq!(output, expected);
+}
+
+#[test]
+fn output_iter() {
+	let bytecode = compile(String::from(include_str!("../samples/hello_world.bf"))).unwrap();
+	let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	let machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	let bytes: Vec<u8> = machine
+		.output_iter(|| None)
+		.collect::<Result<_, _>>()
+		.unwrap();
+	assert_eq!(bytes, b"Hello World!\n");
+}
+
+#[test]
+fn hex_dump_starts_a_new_line_every_16_bytes() {
+	let config = Config {
+		output_mode: OutputMode::HexDump,
+		..Config::default()
+	};
+	let output = run(&".+".repeat(17), b"", &config);
+	assert_eq!(
+		String::from_utf8(output.unwrap()).unwrap(),
+		"00000000: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n00000010: 10\n"
+	);
+}
+
+#[test]
+fn interrupt_stops_with_partial_stats() {
+	let interrupt = Arc::new(AtomicBool::new(false));
+	let config = Config {
+		interrupt: Some(Arc::clone(&interrupt)),
+		..Config::default()
+	};
+	let setter = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(50));
+		interrupt.store(true, Ordering::Relaxed);
+	});
+	let bytecode = compile(String::from("+[]")).unwrap();
+	let (profile, result) = profile_partial(&bytecode, &mut &b""[..], &mut Vec::new(), &config);
+	setter.join().unwrap();
+	assert!(matches!(
+		result.unwrap_err().kind,
+		RuntimeErrorKind::Interrupted
+	));
+	assert!(profile.total_steps > 0);
+	assert_eq!(profile.instruction_counts["AddByte"], 1);
+}
+
+#[test]
+fn timeout() {
+	let config = Config {
+		timeout: Some(Duration::from_millis(50)),
+		..Config::default()
+	};
+	let output = run("+[]", b"", &config);
+	assert!(matches!(
+		output,
+		Err(BfError::Runtime(error)) if matches!(error.kind, RuntimeErrorKind::TimeLimitExceeded)
+	));
+}
+
+#[test]
+fn leading_straight_line_code_folds_into_the_initial_tape() {
+	let source_code = "+".repeat(65) + ">" + &"+".repeat(66) + ">" + &"+".repeat(300) + "[.<]";
+	let config = Config {
+		fold_constants: true,
+		..Config::default()
+	};
+	let folded = compile_with_config(&source_code, &config).unwrap();
+	assert_eq!(folded.initial_tape(), [65, 66, 300]);
+	assert_eq!(folded.initial_data_pointer(), 2);
+	assert!(matches!(
+		folded.instructions()[0],
+		Instruction::OpenBracket { .. }
+	));
+	let naive = compile_str(&source_code).unwrap();
+	let run = |bytecode| {
+		let mut output = Vec::new();
+		execute(bytecode, &mut &b""[..], &mut output, &config).unwrap();
+		output
+	};
+	assert_eq!(run(&folded), b",BA");
+	assert_eq!(run(&folded), run(&naive));
+}
+
+#[test]
+fn folded_code_takes_no_steps() {
+	let config = Config {
+		fold_constants: true,
+		max_steps: Some(1),
+		..Config::default()
+	};
+	let run = |config: &Config| {
+		let bytecode = compile_with_config("+>++>+++.", config).unwrap();
+		execute(&bytecode, &mut &b""[..], &mut Vec::new(), config)
+	};
+	assert!(run(&config).is_ok());
+	let error = run(&Config {
+		fold_constants: false,
+		..config.clone()
+	})
+	.unwrap_err();
+	assert!(matches!(error.kind, RuntimeErrorKind::StepLimitExceeded));
+
+	let config = Config {
+		opt_level: OptLevel::None,
+		..config
+	};
+	let bytecode = compile_with_config("+>++>+++.", &config).unwrap();
+	assert!(bytecode.initial_tape().is_empty());
+	assert_eq!(bytecode.len(), 9);
+}
+
+#[test]
+fn folded_code_respects_tape_limits() {
+	let run = |config: &Config| {
+		let bytecode = compile_with_config(">>>>>>>><<<<<<<<+.", config).unwrap();
+		let mut output = Vec::new();
+		execute(&bytecode, &mut &b""[..], &mut output, config).map(|_| output)
+	};
+	for fold_constants in [false, true] {
+		let config = Config {
+			tape_mode: TapeMode::Fixed(2),
+			fold_constants,
+			..Config::default()
+		};
+		let error = run(&config).unwrap_err();
+		assert!(matches!(error.kind, RuntimeErrorKind::PointerOutOfBounds));
+		let config = Config {
+			max_tape_cells: Some(5),
+			fold_constants,
+			..Config::default()
+		};
+		let error = run(&config).unwrap_err();
+		assert!(matches!(error.kind, RuntimeErrorKind::TapeLimitExceeded));
+	}
+	let folded = Config {
+		fold_constants: true,
+		..Config::default()
+	};
+	assert_eq!(run(&folded).unwrap(), [1]);
+}
+
+#[test]
+fn invalid_utf8_in_a_comment_compiles() {
+	let source_code = b"++ \xff\xc3( comment \x80) .";
+	let bytecode = compile(&source_code[..]).unwrap();
+	assert_eq!(
+		bytecode.instructions(),
+		[Instruction::AddByte(2), Instruction::Output]
+	);
+}
+
+#[test]
+fn offsets_after_invalid_utf8_count_bytes() {
+	let error = compile(b"+\xff\xfe\xc3\xa9]").unwrap_err();
+	assert_eq!(
+		error.kind,
+		CompileErrorKind::UnmatchedCloseBracket { line: 1, column: 6 }
+	);
+	assert_eq!(error.span, Some(5..6));
+
+	let bytecode = compile(b"\xff\n\xfe+.").unwrap();
+	assert_eq!(bytecode.source_offset(0), Some(3));
+	assert_eq!(bytecode.source_position(1), Some((2, 3)));
+}
+
+#[test]
+fn loop_too_deep() {
+	let config = Config {
+		max_loop_depth: Some(10),
+		..Config::default()
+	};
+	let source_code = format!("+{}{}", "[".repeat(50), "]".repeat(50));
+	let error = compile_with_config(source_code, &config).unwrap_err();
+	assert_eq!(
+		error.kind,
+		CompileErrorKind::LoopTooDeep {
+			depth: 11,
+			limit: 10,
+			line: 1,
+			column: 12
+		}
+	);
+	assert!(error.to_string().contains("depth 11"));
+}
+
+#[test]
+fn unbalanced_brackets() {
+	let output = run(
+		include_str!("../samples/unbalanced_brackets.bf"),
+		b"",
+		&Config::default(),
+	);
+	assert!(matches!(
+		output,
+		Err(BfError::Compile(error))
+			if error.kind == CompileErrorKind::UnmatchedOpenBracket { line: 1, column: 1 }
+	));
+}
+
+#[test]
+fn optimization_does_not_change_output() {
+	let samples = [
+		include_str!("../samples/hello_world.bf"),
+		include_str!("../samples/adder.bf"),
+		include_str!("../samples/wrapping_cells.bf"),
+		include_str!("../samples/rot13.bf"),
+	];
+	for source_code in samples {
+		let outputs = [OptLevel::None, OptLevel::Full].map(|opt_level| {
+			let config = Config {
+				opt_level,
+				..Config::default()
+			};
+			let bytecode = compile_with_config(String::from(source_code), &config).unwrap();
+			let mut output = Vec::new();
+			execute(
+				&bytecode,
+				&mut &b"Hello, World!\n"[..],
+				&mut output,
+				&config,
+			)
+			.unwrap();
+			output
+		});
+		assert_eq!(outputs[0], outputs[1]);
+	}
+}
+
+#[test]
+fn restoring_a_snapshot_returns_to_the_prior_state() {
+	let bytecode = compile(String::from(include_str!("../samples/hello_world.bf"))).unwrap();
+	let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	for _ in 0..100 {
+		machine.step().unwrap();
+	}
+	let snapshot = machine.snapshot();
+	let before = (
+		machine.memory().to_vec(),
+		machine.data_pointer(),
+		machine.instruction_pointer(),
+	);
+	machine.run().unwrap();
+	machine.restore(snapshot);
+	let after = (
+		machine.memory().to_vec(),
+		machine.data_pointer(),
+		machine.instruction_pointer(),
+	);
+	assert_eq!(before, after);
+	machine.run().unwrap();
+	drop(machine);
+	assert!(output.ends_with(b"Hello World!\n"));
+}
+
+#[test]
+fn debug_output_goes_to_the_error_sink() {
+	let config = Config {
+		debug: true,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(String::from("++++++++[>++++++++<-]>+.#"), &config).unwrap();
+	let (mut output, mut error_output) = (Vec::new(), Vec::new());
+	execute_with_error_output(
+		&bytecode,
+		&mut &b""[..],
+		&mut output,
+		&mut error_output,
+		&config,
+	)
+	.unwrap();
+	assert_eq!(output, b"A");
+	let error_output = String::from_utf8(error_output).unwrap();
+	assert!(error_output.starts_with("Debug at line 1, column 25\n"));
+	assert!(error_output.contains("65"));
+}
+
+#[test]
+fn cat_runs_as_a_coroutine() {
+	struct SharedInput(Rc<RefCell<VecDeque<u8>>>);
+
+	impl Read for SharedInput {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			self.0.borrow_mut().read(buf)
+		}
+	}
+
+	let queue = Rc::new(RefCell::new(VecDeque::new()));
+	let bytecode = compile(String::from(programs::CAT)).unwrap();
+	let config = Config {
+		eof_mode: EofMode::Zero,
+		..Config::default()
+	};
+	let (mut input, mut output) = (SharedInput(Rc::clone(&queue)), Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	let mut pending = b"abc".iter();
+	let mut echoed = Vec::new();
+	let mut requests = 0;
+	loop {
+		match machine.run_until_input().unwrap() {
+			RunOutcome::NeedsInput => {
+				assert!(queue.borrow().is_empty());
+				requests += 1;
+				queue.borrow_mut().extend(pending.next());
+			}
+			RunOutcome::Output(byte) => echoed.push(byte),
+			RunOutcome::Halted => break,
+		}
+	}
+	drop(machine);
+	assert_eq!(requests, 4);
+	assert_eq!(echoed, b"abc");
+	assert!(output.is_empty());
+}
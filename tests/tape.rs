@@ -0,0 +1,314 @@
+use brainfuck::{
+	assemble, compile, compile_with_config, execute_capturing, CellWidth, Config, LeftBoundary,
+	Machine, OptLevel, RuntimeError, RuntimeErrorKind, TapeMode,
+};
+
+fn run(source_code: &str) -> (Vec<u32>, isize, Vec<u8>) {
+	let bytecode = compile(String::from(source_code)).unwrap();
+	let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	machine.run().unwrap();
+	let memory = machine.memory().to_vec();
+	let data_pointer = machine.data_pointer();
+	(memory, data_pointer, output)
+}
+
+fn run_with_left_boundary(
+	source_code: &str,
+	left_boundary: LeftBoundary,
+) -> Result<(Vec<u32>, isize), RuntimeError> {
+	let config = Config {
+		left_boundary,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(String::from(source_code), &config).unwrap();
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	machine.run()?;
+	Ok((machine.memory().to_vec(), machine.data_pointer()))
+}
+
+/// Steps through `source_code` one unoptimized `+` or `-` at a time and returns the first cell.
+fn first_cell_after(source_code: &str, cell_width: CellWidth) -> u32 {
+	let config = Config {
+		cell_width,
+		opt_level: OptLevel::None,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(source_code, &config).unwrap();
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	while !machine.is_halted() {
+		machine.step().unwrap();
+	}
+	machine.cell(0)
+}
+
+#[test]
+fn cells_wrap_around_at_their_width() {
+	assert_eq!(first_cell_after("-", CellWidth::U8), 255);
+	assert_eq!(first_cell_after(&"+".repeat(256), CellWidth::U8), 0);
+	assert_eq!(first_cell_after("-", CellWidth::U16), 65535);
+	assert_eq!(first_cell_after(&"+".repeat(256), CellWidth::U16), 256);
+	assert_eq!(first_cell_after(&"+".repeat(65536), CellWidth::U16), 0);
+	assert_eq!(first_cell_after("-", CellWidth::U32), u32::MAX);
+	assert_eq!(first_cell_after("-+", CellWidth::U32), 0);
+}
+
+#[test]
+fn growing_left_keeps_pointing_at_the_new_cell() {
+	let (memory, data_pointer, _) = run("<+");
+	assert_eq!(memory, [1, 0]);
+	assert_eq!(data_pointer, -1);
+}
+
+#[test]
+fn value_written_after_growing_left_is_found_again() {
+	let (memory, data_pointer, output) = run("<+>>>>><<<<<.");
+	assert_eq!(memory, [1, 0, 0, 0, 0, 0]);
+	assert_eq!(data_pointer, -1);
+	assert_eq!(output, [1]);
+}
+
+#[test]
+fn growing_left_one_cell_at_a_time_shifts_existing_cells() {
+	let (memory, data_pointer, output) = run("+++<<<++>+.>>.");
+	assert_eq!(memory, [2, 1, 0, 3]);
+	assert_eq!(data_pointer, 0);
+	assert_eq!(output, [1, 3]);
+}
+
+#[test]
+fn multiply_loop_growing_left_returns_to_the_source_cell() {
+	let (memory, data_pointer, output) = run("+++[-<<++>>]<<.>>.");
+	assert_eq!(memory, [6, 0, 0]);
+	assert_eq!(data_pointer, 0);
+	assert_eq!(output, [6, 0]);
+}
+
+#[test]
+fn far_left_and_far_right_cells_are_independent() {
+	let source_code = format!(
+		"{}+{}++{}+++",
+		"<".repeat(100_000),
+		">".repeat(200_000),
+		"<".repeat(100_000)
+	);
+	let bytecode = compile(source_code).unwrap();
+	let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	machine.run().unwrap();
+	assert_eq!(machine.memory().len(), 200_001);
+	assert_eq!(machine.cell(-100_000), 1);
+	assert_eq!(machine.cell(0), 3);
+	assert_eq!(machine.cell(100_000), 2);
+	assert_eq!(machine.cell(-100_001), 0);
+}
+
+#[test]
+fn walking_left_one_cell_at_a_time() {
+	let (memory, data_pointer, _) = run(&"+<".repeat(1000));
+	assert_eq!(memory.len(), 1001);
+	assert_eq!(memory[0], 0);
+	assert!(memory[1..].iter().all(|&cell| cell == 1));
+	assert_eq!(data_pointer, -1000);
+}
+
+#[test]
+fn left_boundary_grow_extends_the_tape() {
+	let (memory, data_pointer) = run_with_left_boundary("<+", LeftBoundary::Grow).unwrap();
+	assert_eq!(memory, [1, 0]);
+	assert_eq!(data_pointer, -1);
+}
+
+#[test]
+fn left_boundary_error_stops_the_program() {
+	let error = run_with_left_boundary(">+<<", LeftBoundary::Error).unwrap_err();
+	assert!(matches!(error.kind, RuntimeErrorKind::PointerOutOfBounds));
+	assert_eq!(error.offset, 2);
+}
+
+#[test]
+fn left_boundary_clamp_stays_on_the_first_cell() {
+	let (memory, data_pointer) = run_with_left_boundary(">+<<<+", LeftBoundary::Clamp).unwrap();
+	assert_eq!(memory, [1, 1]);
+	assert_eq!(data_pointer, 0);
+}
+
+#[test]
+fn left_boundary_clamp_is_not_optimized_into_a_multiplication() {
+	let (memory, data_pointer) = run_with_left_boundary("++[-<+>]", LeftBoundary::Clamp).unwrap();
+	assert_eq!(memory, [2, 0]);
+	assert_eq!(data_pointer, 1);
+}
+
+fn run_with_tape_limit(
+	source_code: &str,
+	max_tape_cells: Option<usize>,
+) -> Result<usize, RuntimeError> {
+	let config = Config {
+		max_tape_cells,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(String::from(source_code), &config).unwrap();
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	let result = machine.run();
+	assert!(machine.memory().len() <= max_tape_cells.unwrap_or(usize::MAX));
+	result.map(|()| machine.memory().len())
+}
+
+#[test]
+fn growing_left_respects_the_tape_limit() {
+	let error = run_with_tape_limit("+[<+]", Some(10)).unwrap_err();
+	assert!(matches!(error.kind, RuntimeErrorKind::TapeLimitExceeded));
+	assert_eq!(run_with_tape_limit("<<<<<<<<<+", Some(10)).unwrap(), 10);
+}
+
+#[test]
+fn huge_left_jump_fails_gracefully() {
+	let bytecode = assemble(&format!("0000 SubPointer {}\n", usize::MAX)).unwrap();
+	let run = |max_tape_cells| {
+		let config = Config {
+			max_tape_cells,
+			..Config::default()
+		};
+		let (mut input, mut output) = (&b""[..], Vec::new());
+		Machine::new(&bytecode, &mut input, &mut output, &config)
+			.run()
+			.unwrap_err()
+			.kind
+	};
+	assert!(matches!(
+		run(Some(100)),
+		RuntimeErrorKind::TapeLimitExceeded
+	));
+	assert!(matches!(run(None), RuntimeErrorKind::OutOfMemory));
+}
+
+#[test]
+fn preallocated_fixed_tape_is_addressable_immediately() {
+	let bytecode = compile(String::from(">>>>>>>+<<<<<<<+")).unwrap();
+	let config = Config {
+		tape_mode: TapeMode::Fixed(8),
+		preallocate_tape: true,
+		..Config::default()
+	};
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	assert_eq!(machine.memory(), &[0; 8]);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[1, 0, 0, 0, 0, 0, 0, 1]);
+
+	let bytecode = compile(String::from(">>>>>>>>")).unwrap();
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	let error = machine.run().unwrap_err();
+	assert!(matches!(error.kind, RuntimeErrorKind::PointerOutOfBounds));
+}
+
+#[test]
+fn grown_cells_hold_the_initial_cell_value() {
+	let config = Config {
+		initial_cell_value: 255,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(String::from("[>.<<.>-]"), &config).unwrap();
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	assert_eq!(machine.cell(0), 255);
+	assert_eq!(machine.cell(5), 255);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[255, 0, 255]);
+	drop(machine);
+	assert_eq!(output.len(), 255 * 2);
+	assert!(output.iter().all(|&byte| byte == 255));
+}
+
+#[test]
+fn final_state_agrees_with_the_machine_on_unvisited_cells() {
+	let config = Config {
+		initial_cell_value: 7,
+		..Config::default()
+	};
+	let bytecode = compile_with_config(">-", &config).unwrap();
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	machine.run().unwrap();
+	let machine_cells: Vec<u32> = (-2..4).map(|index| machine.cell(index)).collect();
+	drop(machine);
+	let state = execute_capturing(&bytecode, &mut &b""[..], &mut Vec::new(), &config).unwrap();
+	let state_cells: Vec<u32> = (-2..4).map(|index| state.cell(index)).collect();
+	assert_eq!(state_cells, machine_cells);
+	assert_eq!(state_cells, [7, 7, 7, 6, 7, 7]);
+}
+
+#[test]
+fn leading_loop_runs_on_a_seeded_tape() {
+	let bytecode = compile(String::from("[.-]")).unwrap();
+	let (mut input, mut output, config) = (&b""[..], Vec::new(), Config::default());
+	let mut machine =
+		Machine::new(&bytecode, &mut input, &mut output, &config).with_memory(vec![2]);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[0]);
+	drop(machine);
+	assert_eq!(output, [2, 1]);
+}
+
+#[test]
+fn input_after_moving_right_writes_to_a_grown_cell() {
+	let bytecode = compile(String::from(">,")).unwrap();
+	let (mut input, mut output, config) = (&b"A"[..], Vec::new(), Config::default());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[0, u32::from(b'A')]);
+	assert_eq!(machine.data_pointer(), 1);
+}
+
+#[test]
+fn initial_tape_is_added_once_across_snapshots() {
+	let config = Config {
+		fold_constants: true,
+		..Config::default()
+	};
+	let bytecode = compile_with_config("+++>++.", &config).unwrap();
+	assert_eq!(bytecode.initial_tape(), [3, 2]);
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&bytecode, &mut input, &mut output, &config);
+	let before = machine.snapshot();
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[3, 2]);
+	let after = machine.snapshot();
+	machine.restore(before);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[3, 2]);
+	// Continuing from a snapshot runs the program again from its start, initial tape included.
+	let mut machine =
+		Machine::new(&bytecode, &mut input, &mut output, &config).with_snapshot(after);
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[3, 5, 2]);
+	machine.reset();
+	machine.run().unwrap();
+	assert_eq!(machine.memory(), &[3, 2]);
+}
+
+#[test]
+fn initial_tape_is_added_on_top_of_another_programs_snapshot() {
+	let config = Config {
+		fold_constants: true,
+		..Config::default()
+	};
+	let (first, second) = (
+		compile_with_config("+.", &config).unwrap(),
+		compile_with_config("+++.", &config).unwrap(),
+	);
+	assert_eq!(second.initial_tape(), [3]);
+	let (mut input, mut output) = (&b""[..], Vec::new());
+	let mut machine = Machine::new(&first, &mut input, &mut output, &config);
+	machine.run().unwrap();
+	let snapshot = machine.snapshot();
+	let mut machine =
+		Machine::new(&second, &mut input, &mut output, &config).with_snapshot(snapshot);
+	machine.run().unwrap();
+	drop(machine);
+	assert_eq!(output, [1, 4]);
+}